@@ -0,0 +1,289 @@
+use std::thread;
+
+use crate::field::{band_rows, Field};
+use crate::rule::AutomataRule;
+use crate::topology::Topology;
+
+const WORD: usize = 64;
+
+/// Bit-packed field with 64 cells per word, each row padded to a whole number of words.
+/// Supports the untwisted topologies, i.e. every edge is either wrapped or dead.
+#[derive(Clone)]
+pub struct BitField {
+    words: Vec<u64>,
+    pub rows: usize,
+    pub columns: usize,
+    stride: usize,
+    topology: Topology,
+}
+
+impl BitField {
+    pub fn with_size(rows: usize, columns: usize) -> BitField {
+        let stride = columns.div_ceil(WORD);
+        BitField { words: vec![0; rows * stride], rows, columns, stride, topology: Topology::Torus }
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> bool {
+        self.words[row * self.stride + column / WORD] >> (column % WORD) & 1 == 1
+    }
+
+    pub fn set(&mut self, row: usize, column: usize, alive: bool) {
+        let word = &mut self.words[row * self.stride + column / WORD];
+        let bit = 1u64 << (column % WORD);
+        if alive { *word |= bit } else { *word &= !bit }
+    }
+
+    pub fn to_cells(&self) -> Vec<bool> {
+        (0..self.rows * self.columns).map(|i| self.get(i / self.columns, i % self.columns)).collect()
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn population(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Indices `row * columns + column` of the cells alive in `next` but not in `self`.
+    pub fn born(&self, next: &BitField) -> Vec<usize> {
+        self.indices(self.words.iter().zip(&next.words).map(|(&old, &new)| new & !old))
+    }
+
+    /// Indices `row * columns + column` of the live cells in order.
+    pub fn alive(&self) -> Vec<usize> {
+        self.indices(self.words.iter().copied())
+    }
+
+    fn indices(&self, words: impl Iterator<Item = u64>) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for (i, mut bits) in words.enumerate() {
+            while bits != 0 {
+                let column = i % self.stride * WORD + bits.trailing_zeros() as usize;
+                indices.push(i / self.stride * self.columns + column);
+                bits &= bits - 1;
+            }
+        }
+        indices
+    }
+
+    pub fn step(&self, rule: &AutomataRule, threads: usize) -> BitField {
+        let mut next = BitField::with_size(self.rows, self.columns);
+        next.topology = self.topology;
+        let rows = band_rows(self.rows, threads);
+
+        thread::scope(|s| {
+            for (b, band) in next.words.chunks_mut(rows * self.stride).enumerate() {
+                s.spawn(move || self.step_band(rule, b * rows, band));
+            }
+        });
+
+        next
+    }
+
+    /// Computes the rows starting at `first` into `band`, which holds whole rows of words.
+    fn step_band(&self, rule: &AutomataRule, first: usize, band: &mut [u64]) {
+        let (birth, survival) = rule.tables();
+        let last_mask = match self.columns % WORD {
+            0 => !0u64,
+            n => (1u64 << n) - 1,
+        };
+
+        let dead = vec![0; self.stride];
+        let row_at = |r, dr| match self.topology.offset((r, 0), (dr, 0), self.rows, self.columns) {
+            Some((r, _)) => self.row(r),
+            None => &dead[..],
+        };
+
+        for (rr, out) in band.chunks_mut(self.stride).enumerate() {
+            let r = first + rr;
+            let up = row_at(r, -1);
+            let mid = self.row(r);
+            let down = row_at(r, 1);
+
+            for (i, word) in out.iter_mut().enumerate() {
+                let n = [
+                    self.west(up, i), up[i], self.east(up, i),
+                    self.west(mid, i), /*          */ self.east(mid, i),
+                    self.west(down, i), down[i], self.east(down, i),
+                ];
+                let count = count_bits(n);
+
+                let alive = mid[i];
+                let born = select(&count, birth);
+                let survives = select(&count, survival);
+                *word = (alive & survives) | (!alive & born);
+
+                if i == self.stride - 1 {
+                    *word &= last_mask;
+                }
+            }
+        }
+    }
+
+    fn row(&self, r: usize) -> &[u64] {
+        &self.words[r * self.stride..(r + 1) * self.stride]
+    }
+
+    /// Word `i` of `row` shifted so that bit `c` holds the cell at column `c - 1`.
+    fn west(&self, row: &[u64], i: usize) -> u64 {
+        let carry = match i {
+            0 if self.topology.wraps_columns() => row[self.stride - 1] >> ((self.columns - 1) % WORD) & 1,
+            0 => 0,
+            _ => row[i - 1] >> (WORD - 1),
+        };
+        (row[i] << 1) | carry
+    }
+
+    /// Word `i` of `row` shifted so that bit `c` holds the cell at column `c + 1`.
+    fn east(&self, row: &[u64], i: usize) -> u64 {
+        match i + 1 == self.stride {
+            true if self.topology.wraps_columns() => (row[i] >> 1) | (row[0] & 1) << ((self.columns - 1) % WORD),
+            true => row[i] >> 1,
+            false => (row[i] >> 1) | (row[i + 1] & 1) << (WORD - 1),
+        }
+    }
+}
+
+impl From<&Field<bool>> for BitField {
+    fn from(field: &Field<bool>) -> Self {
+        let mut bits = BitField::with_size(field.rows, field.columns);
+        bits.topology = field.topology;
+        for (i, &alive) in field.cells.iter().enumerate() {
+            if alive {
+                bits.set(i / field.columns, i % field.columns, true);
+            }
+        }
+        bits
+    }
+}
+
+impl From<&BitField> for Field<bool> {
+    fn from(bits: &BitField) -> Self {
+        Field::new(bits.to_cells(), bits.rows, bits.columns).with_topology(bits.topology)
+    }
+}
+
+fn full_adder(a: u64, b: u64, c: u64) -> (u64, u64) {
+    let ab = a ^ b;
+    (ab ^ c, (a & b) | (c & ab))
+}
+
+fn half_adder(a: u64, b: u64) -> (u64, u64) {
+    (a ^ b, a & b)
+}
+
+/// Adds eight one-bit planes into the four bit planes of the neighbour count (0..=8).
+pub fn count_bits(n: [u64; 8]) -> [u64; 4] {
+    let (sa, ca) = full_adder(n[0], n[1], n[2]);
+    let (sb, cb) = full_adder(n[3], n[4], n[5]);
+    let (sc, cc) = half_adder(n[6], n[7]);
+
+    let (b0, cd) = full_adder(sa, sb, sc);
+    let (t, ce) = full_adder(ca, cb, cc);
+    let (b1, cf) = half_adder(t, cd);
+    let (b2, b3) = half_adder(ce, cf);
+
+    [b0, b1, b2, b3]
+}
+
+/// Mask of all bits whose neighbour count is enabled in `table`.
+pub fn select(count: &[u64; 4], table: &[bool]) -> u64 {
+    table.iter().enumerate().filter(|(_, &on)| on).fold(0, |acc, (n, _)| {
+        acc | count.iter().enumerate().fold(!0u64, |eq, (bit, &plane)| {
+            eq & if n >> bit & 1 == 1 { plane } else { !plane }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitfield::BitField;
+    use crate::field::Field;
+    use crate::rule::AutomataRule;
+    use crate::topology::Topology;
+
+    fn naive_step(field: &Field<bool>, rule: &AutomataRule) -> Vec<bool> {
+        let projection = field.proj2d();
+        let neighbours = field.calculate_neighbours(&projection, rule.neighbourhood());
+        field.apply_rule(neighbours, rule)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let field = Field::from_random(7, 130);
+        let bits = BitField::from(&field);
+        assert_eq!(field.cells, bits.to_cells());
+    }
+
+    #[test]
+    fn test_step_matches_naive() {
+        let rules = ["B3/S23", "B36/S23", "B0/S8", "B2/S", "B012345678/S012345678", "B1357/S02468"];
+        let sizes = [(1, 1), (2, 3), (5, 63), (9, 64), (8, 65), (17, 130), (3, 200)];
+
+        for rule in rules.iter().map(|&r| AutomataRule::from(r)) {
+            for &(rows, columns) in sizes.iter() {
+                let mut field = Field::from_random(rows, columns);
+                let mut bits = BitField::from(&field);
+                for _ in 0..4 {
+                    field = Field::new(naive_step(&field, &rule), rows, columns);
+                    bits = bits.step(&rule, 1);
+                    assert_eq!(field.cells, bits.to_cells(), "{}x{}", rows, columns);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_topologies() {
+        let rule = AutomataRule::cgol();
+        for &topology in [Topology::Plane, Topology::CylinderH, Topology::CylinderV].iter() {
+            for &(rows, columns) in [(1, 1), (6, 64), (20, 70)].iter() {
+                let mut field = Field::from_random(rows, columns).with_topology(topology);
+                let mut bits = BitField::from(&field);
+                for _ in 0..4 {
+                    field.cells = naive_step(&field, &rule);
+                    bits = bits.step(&rule, 2);
+                    assert_eq!(field.cells, bits.to_cells(), "{:?} {}x{}", topology, rows, columns);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_parallel() {
+        let rule = AutomataRule::from("B36/S23");
+        for &(rows, columns) in [(1, 70), (13, 64), (100, 190)].iter() {
+            let bits = BitField::from(&Field::from_random(rows, columns));
+            let expected = bits.step(&rule, 1).to_cells();
+
+            for threads in [2, 3, 16, 500].iter() {
+                assert_eq!(expected, bits.step(&rule, *threads).to_cells(), "{}x{} on {} threads", rows, columns, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn test_glider() {
+        let glider = Field::from_rle("x=5,y=5,rule=B3/S23\nbob$2bo$3o!");
+        let expected = Field::from_rle("x=5,y=5,rule=B3/S23\n$obo$b2o$bo2$!");
+
+        let next = BitField::from(&glider).step(&AutomataRule::cgol(), 1);
+        assert_eq!(expected.cells, next.to_cells());
+    }
+
+    #[test]
+    fn test_born() {
+        let blinker = BitField::from(&Field::from_cells(".....\n..O..\n..O..\n..O..\n....."));
+        let next = blinker.step(&AutomataRule::cgol(), 1);
+        assert_eq!(vec![11, 13], blinker.born(&next));
+        assert_eq!((3, 3), (blinker.population(), next.population()));
+        assert_eq!(vec![7, 12, 17], blinker.alive());
+
+        // Across the end of the first word and round the torus.
+        let mut wide = Field::with_size(5, 70);
+        wide.insert(Field::from_cells("O\nO\nO"), 1, 69);
+        let wide = BitField::from(&wide);
+        assert_eq!(vec![2 * 70, 2 * 70 + 68], wide.born(&wide.step(&AutomataRule::cgol(), 1)));
+    }
+}
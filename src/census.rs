@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use futures::executor::block_on;
+
+use crate::field::Field;
+use crate::game::Game;
+use crate::object::{objects, orient, phases, split, Object};
+use crate::rule::AutomataRule;
+
+/// Generations a soup gets to settle before its objects are counted anyway.
+const MAX_GENERATIONS: usize = 10000;
+/// Generations an object is followed for to find its period.
+const MAX_PERIOD: usize = 100;
+/// Apgcode of objects that don't recur within `MAX_PERIOD` generations.
+const UNKNOWN: &str = "zz_UNKNOWN";
+/// Digits of the extended Wechsler format.
+const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Common objects in Conway's Life by apgcode.
+const NAMES: &[(&str, &str)] = &[
+    ("xs4_33", "block"),
+    ("xs6_696", "beehive"),
+    ("xs7_2596", "loaf"),
+    ("xs5_253", "boat"),
+    ("xs6_356", "ship"),
+    ("xs4_252", "tub"),
+    ("xs8_6996", "pond"),
+    ("xs7_25ac", "long boat"),
+    ("xs6_25a4", "barge"),
+    ("xp2_7", "blinker"),
+    ("xp2_7e", "toad"),
+    ("xp2_318c", "beacon"),
+    ("xq4_153", "glider"),
+];
+
+/// Objects left over by settled soups, counted by apgcode.
+#[derive(Default)]
+pub struct Census {
+    counts: HashMap<String, usize>,
+    soups: usize,
+    /// Apgcodes by shape, as most objects are common ones.
+    known: HashMap<u64, String>,
+}
+
+impl Census {
+    pub fn new() -> Census {
+        Census::default()
+    }
+
+    /// Runs `soup` under `rule` until the field repeats itself and counts the objects left, taking
+    /// apart groups of objects that don't affect each other.
+    pub fn add_soup(&mut self, soup: Field<bool>, rule: &AutomataRule) {
+        if rule.states() > 2 {
+            panic!("The census only supports rules with two states");
+        }
+        let mut game = Game::new(soup, rule.clone());
+        while game.period().is_none() && game.iterations() < MAX_GENERATIONS {
+            block_on(game.next_iteration());
+        }
+
+        let parts = objects(&Field::from_alive(game.field()), rule.neighbourhood().radius()).iter()
+            .flat_map(|object| split(object, rule, MAX_PERIOD))
+            .collect::<Vec<_>>();
+        for object in parts {
+            let code = self.known.entry(object.shape_hash()).or_insert_with(|| apgcode(&object, rule));
+            *self.counts.entry(code.clone()).or_default() += 1;
+        }
+        self.soups += 1;
+    }
+
+    /// Apgcodes and how often they occurred, most common first.
+    pub fn counts(&self) -> Vec<(&str, usize)> {
+        let mut counts = self.counts.iter().map(|(code, &count)| (code.as_str(), count)).collect::<Vec<_>>();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+}
+
+/// A table with a line per object, e.g. `     76  xs4_33                block`.
+impl fmt::Display for Census {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} soups, {} objects", self.soups, self.counts.values().sum::<usize>())?;
+        for (code, count) in self.counts() {
+            writeln!(f, "{:>7}  {:<20}  {}", count, code, name(code).unwrap_or(""))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn name(apgcode: &str) -> Option<&'static str> {
+    NAMES.iter().find(|&&(code, _)| code == apgcode).map(|&(_, name)| name)
+}
+
+/// The apgcode of the object on its own: `xs` and the population for still lifes, `xp` or `xq` and
+/// the period for oscillators and spaceships, then the shortest extended Wechsler code of all its
+/// phases, rotations and reflections.
+pub fn apgcode(object: &Object, rule: &AutomataRule) -> String {
+    let (phases, moved) = match phases(object, rule, MAX_PERIOD) {
+        Some(found) => found,
+        None => return UNKNOWN.to_string(),
+    };
+    let prefix = match (moved, phases.len()) {
+        (false, 1) => format!("xs{}", object.cells.len()),
+        (false, period) => format!("xp{}", period),
+        (true, period) => format!("xq{}", period),
+    };
+    let code = phases.iter()
+        .flat_map(orientations)
+        .map(|cells| wechsler(&cells))
+        .min_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+        .unwrap();
+    format!("{}_{}", prefix, code)
+}
+
+/// The phase's cells rotated and reflected in all 8 ways.
+fn orientations(phase: &Object) -> Vec<Vec<(usize, usize)>> {
+    (0..8).map(|i| {
+        phase.cells.iter().map(|&(r, c, _)| orient(i, (r, c), (phase.height, phase.width))).collect()
+    }).collect()
+}
+
+/// Extended Wechsler format: strips of 5 rows separated by `z`, one digit per column with the top
+/// row as the lowest bit, and runs of `0` shortened to `w` for two, `x` for three and `y` and a
+/// digit for 4 to 39.
+fn wechsler(cells: &[(usize, usize)]) -> String {
+    let height = cells.iter().map(|&(r, _)| r + 1).max().unwrap_or(0);
+    let width = cells.iter().map(|&(_, c)| c + 1).max().unwrap_or(0);
+
+    let strips = (0..height.div_ceil(5)).map(|strip| {
+        let mut columns = vec![0; width];
+        for &(r, c) in cells.iter().filter(|&&(r, _)| r / 5 == strip) {
+            columns[c] |= 1 << (r % 5);
+        }
+        while columns.last() == Some(&0) {
+            columns.pop();
+        }
+
+        let mut code = String::new();
+        let mut zeros = 0;
+        for &column in columns.iter().chain(&[1]) {
+            if column == 0 {
+                zeros += 1;
+                continue;
+            }
+            while zeros >= 4 {
+                let run = zeros.min(39);
+                code.push('y');
+                code.push(DIGITS[run - 4] as char);
+                zeros -= run;
+            }
+            code.push_str(["", "0", "w", "x"][zeros]);
+            zeros = 0;
+            code.push(DIGITS[column] as char);
+        }
+        code.pop();
+        code
+    });
+    strips.collect::<Vec<_>>().join("z")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::census::{apgcode, name, wechsler, Census};
+    use crate::field::Field;
+    use crate::object::objects;
+    use crate::rule::AutomataRule;
+    use crate::topology::Topology;
+
+    fn code(cells: &str) -> String {
+        let field = Field::from_alive(&Field::from_cells(cells).with_topology(Topology::Plane));
+        apgcode(&objects(&field, 1)[0], &AutomataRule::cgol())
+    }
+
+    #[test]
+    fn test_wechsler() {
+        assert_eq!("33", wechsler(&[(0, 0), (0, 1), (1, 0), (1, 1)]));
+        assert_eq!("1w1", wechsler(&[(0, 0), (0, 3)]));
+        assert_eq!("1y01", wechsler(&[(0, 0), (0, 5)]));
+        assert_eq!("1z1", wechsler(&[(0, 0), (5, 0)]));
+    }
+
+    #[test]
+    fn test_apgcode() {
+        assert_eq!("xs4_33", code("OO\nOO"));
+        assert_eq!("xs6_696", code(".OO.\nO..O\n.OO."));
+        assert_eq!("xp2_7", code("OOO"));
+        assert_eq!("xq4_153", code("..O\nO.O\n.OO"));
+        assert_eq!("xp2_7e", code(".OOO\nOOO."));
+        assert_eq!(Some("glider"), name(&code(".O.\nO..\nOOO")));
+        assert_eq!("zz_UNKNOWN", code("OO"));
+    }
+
+    #[test]
+    fn test_apgcode_split_phases() {
+        // In its vertical phase the blinker is too far from the block to be one object with it.
+        assert_eq!("xp2_33ws", code("OOO...\n......\n....OO\n....OO"));
+        assert_eq!("xp2_7x7", code("O\nO\nO\n.\nO\nO\nO"));
+    }
+
+    #[test]
+    fn test_census() {
+        // The glider's diagonal path round the torus misses the block and the blinker.
+        let mut soup = Field::with_size(30, 30);
+        soup.insert(Field::from_cells("OO\nOO"), 1, 1);
+        soup.insert(Field::from_cells("O\nO\nO"), 1, 10);
+        soup.insert(Field::from_cells(".O.\n..O\nOOO"), 12, 0);
+        let mut census = Census::new();
+        census.add_soup(soup.clone(), &AutomataRule::cgol());
+        census.add_soup(soup, &AutomataRule::cgol());
+        assert_eq!(vec![("xp2_7", 2), ("xq4_153", 2), ("xs4_33", 2)], census.counts());
+        assert!(census.to_string().starts_with("2 soups, 6 objects\n      2  xp2_7                 blinker\n"));
+    }
+
+    #[test]
+    fn test_census_still_life_next_to_oscillator() {
+        let mut soup = Field::with_size(20, 20);
+        soup.insert(Field::from_cells("OOO...\n......\n....OO\n....OO"), 5, 5);
+        let mut census = Census::new();
+        census.add_soup(soup, &AutomataRule::cgol());
+        assert_eq!(vec![("xp2_7", 1), ("xs4_33", 1)], census.counts());
+    }
+
+    #[test]
+    fn test_census_pseudo_objects() {
+        let mut soup = Field::with_size(20, 20);
+        soup.insert(Field::from_cells("OO.OO\nOO.OO"), 5, 5);
+        let mut census = Census::new();
+        census.add_soup(soup, &AutomataRule::cgol());
+        assert_eq!(vec![("xs4_33", 2)], census.counts());
+
+        // The halves of a beacon and of an aircraft carrier don't touch but need each other.
+        let mut soup = Field::with_size(30, 30);
+        soup.insert(Field::from_cells("OO..\nO...\n...O\n..OO"), 5, 5);
+        soup.insert(Field::from_cells("OO..\nO..O\n..OO"), 20, 20);
+        let mut census = Census::new();
+        census.add_soup(soup, &AutomataRule::cgol());
+        assert_eq!(vec![("xp2_318c", 1), ("xs6_39c", 1)], census.counts());
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+/// Hashes of the most recent generations, at most `window` of them, to notice when the field repeats itself.
+pub struct CycleDetector {
+    hashes: VecDeque<(u64, usize)>,
+    window: usize,
+}
+
+impl CycleDetector {
+    pub fn new(window: usize) -> CycleDetector {
+        CycleDetector { hashes: VecDeque::new(), window }
+    }
+
+    /// Records the cell states of the generation at `iterations`, in whatever form the engine keeps them,
+    /// and returns the number of generations since the same states were last seen, i.e. the period, if
+    /// they were seen within the window.
+    pub fn push<T: Hash>(&mut self, states: &[T], iterations: usize) -> Option<usize> {
+        let mut hasher = DefaultHasher::new();
+        states.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let period = self.hashes.iter().rev()
+            .find(|&&(h, _)| h == hash)
+            .map(|&(_, seen)| iterations - seen);
+
+        if self.hashes.len() == self.window {
+            self.hashes.pop_front();
+        }
+        self.hashes.push_back((hash, iterations));
+        period
+    }
+
+    /// Forgets all generations, e.g. after the field was edited.
+    pub fn clear(&mut self) {
+        self.hashes.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cycle::CycleDetector;
+
+    #[test]
+    fn test_push() {
+        let mut cycles = CycleDetector::new(3);
+        assert_eq!(None, cycles.push(&[0, 1, 0], 0));
+        assert_eq!(None, cycles.push(&[1, 1, 0], 1));
+        assert_eq!(Some(2), cycles.push(&[0, 1, 0], 2));
+        assert_eq!(Some(1), cycles.push(&[0, 1, 0], 3));
+
+        // The second generation has left the window of three.
+        assert_eq!(None, cycles.push(&[0, 0, 0], 4));
+        assert_eq!(None, cycles.push(&[1, 1, 0], 5));
+        cycles.clear();
+        assert_eq!(None, cycles.push(&[1, 1, 0], 6));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::game::Game;
+use crate::input::Key;
+use crate::term::gfx_cursor_at;
+use crate::viewport::Viewport;
+
+/// Cursor of the cell editor in field coordinates. The viewport shows one cell per character
+/// while editing, so that mouse clicks reach every cell.
+pub struct Editor {
+    pub row: usize,
+    pub column: usize,
+    /// Zoom level to return to when leaving the editor.
+    pub zoom: usize,
+}
+
+impl Editor {
+    pub fn new(viewport: &Viewport) -> Editor {
+        Editor { row: viewport.top, column: viewport.left, zoom: viewport.zoom }
+    }
+
+    /// Moves the cursor by `(dr, dc)` cells, wrapping around the edges of the field.
+    pub fn move_by(&mut self, (dr, dc): (i32, i32), rows: usize, columns: usize) {
+        self.row = (self.row as i64 + dr as i64).rem_euclid(rows as i64) as usize;
+        self.column = (self.column as i64 + dc as i64).rem_euclid(columns as i64) as usize;
+    }
+
+    /// Keeps the cursor on its cell when the field is resized from `old` to `new` rows and columns
+    /// with its cells centred, or moves it to the nearest cell left if its own was cut off.
+    pub fn resize(&mut self, old: (usize, usize), new: (usize, usize)) {
+        let shift = |at: usize, old: usize, new: usize| (at as i64 + (new as i64 - old as i64) / 2).min(new as i64 - 1).max(0) as usize;
+        self.row = shift(self.row, old.0, new.0);
+        self.column = shift(self.column, old.1, new.1);
+    }
+
+    /// Applies an editing key to the cursor or the field of `game`, returning whether anything changed.
+    /// Clicks toggle a cell, dragging the mouse draws live cells. The viewport follows the cursor.
+    pub fn apply(&mut self, key: Key, game: &mut Game, viewport: &mut Viewport) -> bool {
+        let (rows, columns) = (game.field().rows, game.field().columns);
+        match key {
+            Key::Move(dr, dc) => {
+                self.move_by((dr, dc), rows, columns);
+                viewport.follow(self.row, self.column, rows, columns);
+            }
+            Key::Toggle => {
+                let alive = game.field().cells[self.row * columns + self.column];
+                game.set_cell(self.row, self.column, !alive);
+            }
+            Key::Mouse { x, y, drag } => match viewport.cell_at(x, y, rows, columns) {
+                Some((row, column)) => {
+                    self.row = row;
+                    self.column = column;
+                    let alive = drag || !game.field().cells[row * columns + column];
+                    game.set_cell(row, column, alive);
+                }
+                None => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// Places the terminal cursor on the character showing the cursor cell.
+    pub fn gfx_cursor(&self, viewport: &Viewport) -> String {
+        let (row, column) = viewport.position_of(self.row, self.column);
+        gfx_cursor_at(row, column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::Editor;
+    use crate::field::Field;
+    use crate::game::Game;
+    use crate::input::Key;
+    use crate::rule::AutomataRule;
+    use crate::viewport::Viewport;
+
+    #[test]
+    fn test_move_by() {
+        let mut editor = Editor::new(&Viewport::new(5, 10, 2));
+        editor.move_by((-1, 0), 10, 20);
+        assert_eq!((9, 0), (editor.row, editor.column));
+        editor.move_by((0, 3), 10, 20);
+        assert_eq!((9, 3), (editor.row, editor.column));
+        assert_eq!("\x1B[6;2H\x1B[?25h", editor.gfx_cursor(&Viewport::new(5, 10, 2)));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut game = Game::new(Field::with_size(4, 6), AutomataRule::cgol());
+        let mut viewport = Viewport::new(4, 6, 1);
+        let mut editor = Editor::new(&viewport);
+
+        assert!(editor.apply(Key::Move(1, -1), &mut game, &mut viewport));
+        assert!(editor.apply(Key::Toggle, &mut game, &mut viewport));
+        assert!(game.field().cells[6 + 5]);
+        assert!(editor.apply(Key::Toggle, &mut game, &mut viewport));
+        assert!(!game.field().cells[6 + 5]);
+
+        assert!(editor.apply(Key::Mouse { x: 3, y: 2, drag: false }, &mut game, &mut viewport));
+        assert!(editor.apply(Key::Mouse { x: 3, y: 2, drag: true }, &mut game, &mut viewport));
+        assert!(editor.apply(Key::Mouse { x: 4, y: 2, drag: true }, &mut game, &mut viewport));
+        assert!(!editor.apply(Key::Mouse { x: 4, y: 9, drag: true }, &mut game, &mut viewport));
+        assert!(!editor.apply(Key::Pause, &mut game, &mut viewport));
+        assert_eq!((0, 3), (editor.row, editor.column));
+
+        let alive = game.field().cells.iter().enumerate().filter(|(_, &a)| a).map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(vec![2, 3], alive);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut game = Game::new(Field::with_size(10, 10), AutomataRule::cgol());
+        let mut editor = Editor::new(&Viewport::new(10, 10, 1));
+        editor.move_by((4, 8), 10, 10);
+        game.set_cell(editor.row, editor.column, true);
+
+        game.resize(6, 14);
+        editor.resize((10, 10), (6, 14));
+        assert_eq!((2, 10), (editor.row, editor.column));
+        assert!(game.field().cells[editor.row * 14 + editor.column]);
+
+        editor.resize((6, 14), (2, 2));
+        assert_eq!((0, 1), (editor.row, editor.column));
+    }
+}
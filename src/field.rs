@@ -1,28 +1,39 @@
-use std::{fs, io};
-use std::cmp::min;
+use std::{fs, io, thread};
 use std::ffi::OsStr;
 use std::path::Path;
 
 use itertools::Itertools;
 use pest::Parser;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
 
-use crate::rule::AutomataRule;
+use crate::object::{objects, orient, phases};
+use crate::rule::{AutomataRule, Neighbourhood};
+use crate::topology::Topology;
 
 #[derive(Parser)]
 #[grammar = "../rle.pest"]
 struct RleParser;
 
+/// A pattern to look for, `None` for don't care cells that match anything.
+pub type Pattern = Field<Option<bool>>;
+
 #[derive(Clone)]
 pub struct Field<T> {
     pub cells: Vec<T>,
     pub rows: usize,
     pub columns: usize,
+    pub topology: Topology,
 }
 
 impl<T> Field<T> {
     pub fn new(cells: Vec<T>, rows: usize, columns: usize) -> Field<T> {
-        Field { cells, rows, columns }
+        Field { cells, rows, columns, topology: Topology::Torus }
+    }
+
+    pub fn with_topology(mut self, topology: Topology) -> Field<T> {
+        self.topology = topology;
+        self
     }
 
     pub fn with_size(rows: usize, columns: usize) -> Field<T>
@@ -37,52 +48,132 @@ impl<T> Field<T> {
     {
         let pattern_2d = pattern.proj2d();
 
-        for r in 0..min(pattern.rows, self.rows) {
-            for c in 0..min(pattern.columns, self.columns) {
-                let rr = wrap(r, row as i32, self.rows);
-                let cc = wrap(c, column as i32, self.columns);
-                self.cells[rr * self.columns + cc] = pattern_2d[r][c];
+        for (r, pattern_row) in pattern_2d.iter().enumerate().take(self.rows) {
+            for (c, &cell) in pattern_row.iter().enumerate().take(self.columns) {
+                if let Some((rr, cc)) = self.topology.offset((row, column), (r as i32, c as i32), self.rows, self.columns) {
+                    self.cells[rr * self.columns + cc] = cell;
+                }
+            }
+        }
+    }
+
+    /// Copy with `rows` x `columns` cells and the old ones centred in it, cropped or padded with defaults.
+    pub fn resized(&self, rows: usize, columns: usize) -> Field<T>
+        where T: Default + Copy
+    {
+        let mut field = Field::with_size(rows, columns).with_topology(self.topology);
+        let dr = (rows as i64 - self.rows as i64) / 2;
+        let dc = (columns as i64 - self.columns as i64) / 2;
+
+        for (i, &cell) in self.cells.iter().enumerate() {
+            let (r, c) = ((i / self.columns) as i64 + dr, (i % self.columns) as i64 + dc);
+            if r >= 0 && r < rows as i64 && c >= 0 && c < columns as i64 {
+                field.cells[r as usize * columns + c as usize] = cell;
+            }
+        }
+        field
+    }
+
+    /// The field rotated and reflected in all 8 ways, leaving out repeats of symmetric ones.
+    pub fn orientations(&self) -> Vec<Field<T>>
+        where T: Copy + PartialEq
+    {
+        let mut orientations: Vec<Field<T>> = Vec::new();
+        for i in 0..8 {
+            let (rows, columns) = if i & 4 == 0 { (self.rows, self.columns) } else { (self.columns, self.rows) };
+            let mut cells = self.cells.clone();
+            for (idx, &cell) in self.cells.iter().enumerate() {
+                let (r, c) = orient(i, (idx / self.columns, idx % self.columns), (self.rows, self.columns));
+                cells[r * columns + c] = cell;
+            }
+
+            let orientation = Field::new(cells, rows, columns).with_topology(self.topology);
+            if !orientations.contains(&orientation) {
+                orientations.push(orientation);
             }
         }
+        orientations
     }
 
     pub fn proj2d(&self) -> Vec<&[T]> {
         self.cells.chunks(self.columns).collect::<Vec<&[T]>>()
     }
 
-    pub fn find_pattern(&self, pattern: &Field<T>) -> Vec<(usize, usize)>
-        where T: Eq
+    /// Top left corners of the pattern's occurrences, its `None` cells matching anything. With `isolated`
+    /// the cells right around it have to be dead, i.e. default, too; beyond the edges of a plane they are.
+    pub fn find_pattern(&self, pattern: &Field<Option<T>>, isolated: bool) -> Vec<(usize, usize)>
+        where T: Eq + Default
     {
         let cells_2d = self.proj2d();
         let pattern_2d = pattern.proj2d();
+        let dead = T::default();
+        let border = isolated as i32;
 
         let mut matches: Vec<(usize, usize)> = Vec::new();
         for r in 0..self.rows {
             for c in 0..self.columns {
-                let mut matching_cells = 0;
-                'p: for rr in 0..pattern.rows {
-                    for cc in 0..pattern.columns {
-                        let rrr = wrap(r, rr as i32, self.rows);
-                        let ccc = wrap(c, cc as i32, self.columns);
-                        if cells_2d[rrr][ccc] != pattern_2d[rr][cc] {
-                            break 'p;
-                        } else {
-                            matching_cells += 1;
-                        }
+                let matching = (-border..pattern.rows as i32 + border).all(|rr| (-border..pattern.columns as i32 + border).all(|cc| {
+                    let inside = rr >= 0 && cc >= 0 && rr < pattern.rows as i32 && cc < pattern.columns as i32;
+                    let expected = match inside {
+                        true => pattern_2d[rr as usize][cc as usize].as_ref(),
+                        false => Some(&dead),
+                    };
+                    match (expected, self.topology.offset((r, c), (rr, cc), self.rows, self.columns)) {
+                        (None, _) => true,
+                        (Some(cell), Some((rrr, ccc))) => cells_2d[rrr][ccc] == *cell,
+                        (Some(_), None) => !inside,
                     }
-                }
-                if matching_cells == pattern.rows * pattern.columns {
+                }));
+                if matching {
                     matches.push((r, c));
                 }
             }
         }
         matches
     }
+
+    pub fn map<U>(&self, f: impl Fn(T) -> U) -> Field<U>
+        where T: Copy
+    {
+        Field::new(self.cells.iter().map(|&cell| f(cell)).collect(), self.rows, self.columns).with_topology(self.topology)
+    }
 }
 
 impl Field<bool> {
     pub fn from_random(rows: usize, columns: usize) -> Field<bool> {
-        let mut rng = rand::rng();
+        Field::from_rng(rows, columns, &mut rand::rng())
+    }
+
+    /// A random field that is the same for the same seed.
+    pub fn from_seed(rows: usize, columns: usize, seed: u64) -> Field<bool> {
+        Field::from_rng(rows, columns, &mut StdRng::seed_from_u64(seed))
+    }
+
+    /// The pattern in every phase it goes through under `rule` until it recurs, possibly elsewhere,
+    /// within `max_period` generations, each with the dead border of the original. Patterns that don't
+    /// recur or aren't a single object are their only phase.
+    pub fn phases(&self, rule: &AutomataRule, max_period: usize) -> Vec<Field<bool>> {
+        let reach = rule.neighbourhood().radius().max(1);
+        let object = match objects(&Field::from_alive(&self.clone().with_topology(Topology::Plane)), reach).as_slice() {
+            [object] => object.clone(),
+            _ => return vec![self.clone()],
+        };
+        let (top, left) = (object.row, object.column);
+        let (bottom, right) = (self.rows - top - object.height, self.columns - left - object.width);
+
+        let phases = match phases(&object, rule, max_period) {
+            Some((phases, _)) => phases,
+            None => return vec![self.clone()],
+        };
+        let others = phases.iter().skip(1).map(|current| {
+            let mut phase = Field::with_size(top + current.height + bottom, left + current.width + right).with_topology(self.topology);
+            phase.insert(current.to_field(0), top, left);
+            phase
+        });
+        std::iter::once(self.clone()).chain(others).collect()
+    }
+
+    fn from_rng<R: RngExt>(rows: usize, columns: usize, rng: &mut R) -> Field<bool> {
         let cells = (0..columns * rows).map(|_| rng.random_bool(0.5)).collect::<Vec<bool>>();
 
         Field::new(cells, rows, columns)
@@ -115,7 +206,129 @@ impl Field<bool> {
         Ok(field)
     }
 
+    /// Don't care cells are dead.
     pub fn from_cells(pattern: &str) -> Field<bool> {
+        Field::pattern_from_cells(pattern).map(|cell| cell.unwrap_or(false))
+    }
+
+    pub fn from_rle(pattern: &str) -> Field<bool> {
+        Field::pattern_from_rle(pattern).map(|cell| cell.unwrap_or(false))
+    }
+
+    /// Writes the field as `.cells` or `.rle` depending on the extension, the latter with `rule` in its header.
+    pub fn to_file(&self, filepath: &str, rule: &str) -> io::Result<()> {
+        let raw = match writable_extension(filepath)? {
+            "cells" => self.to_cells(),
+            _ => self.to_rle(rule),
+        };
+
+        fs::write(filepath, raw)
+    }
+
+    pub fn to_cells(&self) -> String {
+        self.cells.chunks(self.columns)
+            .map(|row| row.iter().map(|&alive| if alive { 'O' } else { '.' }).collect::<String>() + "\n")
+            .collect()
+    }
+
+    /// Run-length encodes the whole field, leaving out dead cells at the end of rows and lines no longer than 70 characters.
+    pub fn to_rle(&self, rule: &str) -> String {
+        let run = |n: usize, tag: char| if n == 1 { tag.to_string() } else { format!("{}{}", n, tag) };
+
+        let mut runs = Vec::new();
+        let mut pending_rows = 0;
+        for row in self.cells.chunks(self.columns) {
+            if row.iter().any(|&alive| alive) {
+                if pending_rows > 0 {
+                    runs.push(run(pending_rows, '$'));
+                }
+                let last = row.iter().rposition(|&alive| alive).unwrap();
+                for (n, &alive) in row[..=last].iter().dedup_with_count() {
+                    runs.push(run(n, if alive { 'o' } else { 'b' }));
+                }
+                pending_rows = 0;
+            }
+            pending_rows += 1;
+        }
+        runs.push("!".to_string());
+
+        let mut rle = format!("x = {}, y = {}, rule = {}\n", self.columns, self.rows, rule);
+        let mut line = String::new();
+        for run in runs {
+            if line.len() + run.len() > 70 {
+                rle += &line;
+                rle.push('\n');
+                line.clear();
+            }
+            line += &run;
+        }
+        rle + &line + "\n"
+    }
+
+    pub fn calculate_neighbours(&self, cells_2d: &[&[bool]], neighbourhood: &Neighbourhood) -> Vec<usize> {
+        let offsets = neighbourhood.offsets();
+        self.cells.iter().enumerate().map(|(i, _)| neighbours(cells_2d, i % self.columns, i / self.columns, self.topology, offsets)).collect()
+    }
+
+    pub fn apply_rule(&self, neighbour_field: Vec<usize>, rule: &AutomataRule) -> Vec<bool> {
+        self.cells.iter().zip(neighbour_field).map(|(&alive, neighbours)| rule.apply(alive, neighbours)).collect()
+    }
+
+    pub fn calculate_configurations(&self, cells_2d: &[&[bool]]) -> Vec<u8> {
+        self.cells.iter().enumerate().map(|(i, _)| configuration(cells_2d, i % self.columns, i / self.columns, self.topology)).collect()
+    }
+
+    pub fn apply_rule_configurations(&self, configuration_field: Vec<u8>, rule: &AutomataRule) -> Vec<bool> {
+        self.cells.iter().zip(configuration_field).map(|(&alive, configuration)| rule.apply_configuration(alive, configuration)).collect()
+    }
+
+    /// Counts neighbours and applies `rule` in bands of rows, one band per thread.
+    pub fn next_generation(&self, rule: &AutomataRule, threads: usize) -> Vec<bool> {
+        let cells_2d = self.proj2d();
+        if threads <= 1 && rule.is_totalistic() {
+            return self.apply_rule(self.calculate_neighbours(&cells_2d, rule.neighbourhood()), rule);
+        } else if threads <= 1 {
+            return self.apply_rule_configurations(self.calculate_configurations(&cells_2d), rule);
+        }
+
+        let band = band_rows(self.rows, threads) * self.columns;
+        let mut new_cells = vec![false; self.cells.len()];
+
+        thread::scope(|s| {
+            for (b, chunk) in new_cells.chunks_mut(band).enumerate() {
+                let cells_2d = &cells_2d;
+                s.spawn(move || {
+                    for (i, cell) in chunk.iter_mut().enumerate() {
+                        let idx = b * band + i;
+                        let (x, y) = (idx % self.columns, idx / self.columns);
+                        *cell = match rule.is_totalistic() {
+                            true => rule.apply(self.cells[idx], neighbours(cells_2d, x, y, self.topology, rule.neighbourhood().offsets())),
+                            false => rule.apply_configuration(self.cells[idx], configuration(cells_2d, x, y, self.topology)),
+                        };
+                    }
+                });
+            }
+        });
+
+        new_cells
+    }
+}
+
+impl Pattern {
+    /// A pattern from a `.cells` or `.rle` file with `None` for the don't care cells written as `?`.
+    pub fn pattern_from_file(filepath: &str) -> io::Result<Pattern> {
+        let raw = fs::read_to_string(filepath)?;
+
+        let field = match Path::new(filepath).extension().and_then(OsStr::to_str).unwrap() {
+            "cells" => Field::pattern_from_cells(raw.as_str()),
+            "rle" => Field::pattern_from_rle(raw.as_str()),
+            unknown => panic!(".{} file support not implemented", unknown)
+        };
+
+        Ok(field)
+    }
+
+    pub fn pattern_from_cells(pattern: &str) -> Pattern {
         let lines: Vec<&str> = pattern.lines()
             .filter(|&l| !l.starts_with('!'))
             .map(|l| l.trim_end())
@@ -124,18 +337,22 @@ impl Field<bool> {
         let rows = lines.len();
         let columns = lines.iter().map(|&l| l.len()).max().expect("Couldn't read pattern file");
 
-        let mut cells = vec![false; rows * columns];
+        let mut cells = vec![Some(false); rows * columns];
 
         for (y, &line) in lines.iter().enumerate() {
             for (x, alive) in line.chars().enumerate() {
-                cells[x + y * columns] = alive == 'O';
+                cells[x + y * columns] = match alive {
+                    'O' => Some(true),
+                    '?' => None,
+                    _ => Some(false),
+                };
             }
         }
 
-        Field { cells, rows, columns }
+        Field::new(cells, rows, columns)
     }
 
-    pub fn from_rle(pattern: &str) -> Field<bool> {
+    pub fn pattern_from_rle(pattern: &str) -> Pattern {
         let pairs = RleParser::parse(Rule::doc, pattern).unwrap_or_else(|e| panic!("{}", e));
 
         let mut rows = 0;
@@ -153,7 +370,7 @@ impl Field<bool> {
             }
         }
 
-        let mut cells = vec![false; rows * columns];
+        let mut cells = vec![Some(false); rows * columns];
         for pair in pairs {
             if let Rule::pattern = pair.as_rule() {
                 let mut r = 0usize;
@@ -185,7 +402,11 @@ impl Field<bool> {
                             }
                             any => {
                                 let pos = c + r * columns;
-                                cells[pos] = any == 'o';
+                                cells[pos] = match any {
+                                    'o' => Some(true),
+                                    '?' => None,
+                                    _ => Some(false),
+                                };
                                 c += 1;
                             }
                         });
@@ -193,20 +414,37 @@ impl Field<bool> {
                 }
             }
         }
-        Field { cells, rows, columns }
+        Field::new(cells, rows, columns)
     }
 
-    pub fn calculate_neighbours(&self, cells_2d: &[&[bool]]) -> Vec<usize> {
-        self.cells.iter().enumerate().map(|(i, _)| neighbours(cells_2d, i % self.columns, i / self.columns)).collect()
+}
+
+impl Field<u8> {
+    /// Generations states of a two-state field, 1 for alive and 0 for dead cells.
+    pub fn from_alive(field: &Field<bool>) -> Field<u8> {
+        Field::new(field.cells.iter().map(|&alive| alive as u8).collect(), field.rows, field.columns)
+            .with_topology(field.topology)
     }
 
-    pub fn apply_rule(&self, neighbour_field: Vec<usize>, rule: &AutomataRule) -> Vec<bool> {
-        self.cells.iter().zip(neighbour_field).map(|(&alive, neighbours)| rule.apply(alive, neighbours)).collect()
+    /// Advances the state of every cell, where `alive` is the rule applied to the live cells only.
+    /// `crate::rule::Rule` is spelt out as the `.rle` parser's grammar rules are called `Rule` here.
+    pub fn next_states(&self, alive: &[bool], rule: &crate::rule::Rule) -> Vec<u8> {
+        self.cells.iter().zip(alive).map(|(&state, &alive)| rule.next_state(state, alive)).collect()
     }
 }
 
-pub fn wrap(pos: usize, delta: i32, lim: usize) -> usize {
-    (pos as i32 + delta).rem_euclid(lim as i32) as usize
+/// Number of rows per band when splitting `rows` across `threads` workers.
+pub fn band_rows(rows: usize, threads: usize) -> usize {
+    rows.div_ceil(threads.max(1)).max(1)
+}
+
+/// The extension of `filepath` if fields can be written to it, i.e. `cells` or `rle`.
+pub fn writable_extension(filepath: &str) -> io::Result<&'static str> {
+    match Path::new(filepath).extension().and_then(OsStr::to_str) {
+        Some("cells") => Ok("cells"),
+        Some("rle") => Ok("rle"),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("\"{}\" is neither a .rle nor a .cells file", filepath))),
+    }
 }
 
 fn gaussian_2d(x: f64, y: f64, x0: f64, y0: f64, sx: f64, sy: f64) -> f64 {
@@ -215,26 +453,29 @@ fn gaussian_2d(x: f64, y: f64, x0: f64, y0: f64, sx: f64, sy: f64) -> f64 {
     (-((x - x0).powi(2) / two_sigma_sq_x + (y - y0).powi(2) / two_sigma_sq_y)).exp()
 }
 
-fn neighbours(cells_2d: &[&[bool]], x: usize, y: usize) -> usize {
+fn neighbours(cells_2d: &[&[bool]], x: usize, y: usize, topology: Topology, offsets: &[(i32, i32)]) -> usize {
     let r = cells_2d.len();
     let c = cells_2d[0].len();
 
-    let xm1 = wrap(x, -1, c);
-    let ym1 = wrap(y, -1, r);
-    let xp1 = wrap(x, 1, c);
-    let yp1 = wrap(y, 1, r);
-
-    [
-        (xm1, ym1), (x, ym1), (xp1, ym1),
-        (xm1, y), /*       */ (xp1, y),
-        (xm1, yp1), (x, yp1), (xp1, yp1)
-    ]
+    offsets
         .iter()
-        .map(|(x, y)| cells_2d[*y][*x])
-        .filter(|i| *i)
+        .filter_map(|&delta| topology.offset((y, x), delta, r, c))
+        .filter(|&(y, x)| cells_2d[y][x])
         .count()
 }
 
+/// The eight neighbours as bits in the order N, NE, E, SE, S, SW, W, NW.
+fn configuration(cells_2d: &[&[bool]], x: usize, y: usize, topology: Topology) -> u8 {
+    let r = cells_2d.len();
+    let c = cells_2d[0].len();
+
+    [(-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1)]
+        .iter()
+        .enumerate()
+        .filter_map(|(bit, &delta)| topology.offset((y, x), delta, r, c).map(|(y, x)| (bit, cells_2d[y][x])))
+        .fold(0, |acc, (bit, alive)| acc | (alive as u8) << bit)
+}
+
 impl<T: PartialEq> PartialEq for Field<T> {
     fn eq(&self, other: &Self) -> bool {
         self.rows == other.rows && self.columns == other.columns && self.cells == other.cells
@@ -243,56 +484,173 @@ impl<T: PartialEq> PartialEq for Field<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::field::{Field, neighbours};
+    use crate::field::{band_rows, configuration, Field, neighbours, writable_extension};
     use crate::game::Game;
-    use crate::rule::AutomataRule;
+    use crate::rule::{AutomataRule, HEXAGONAL, MOORE, Rule, VON_NEUMANN};
+    use crate::topology::Topology;
 
     #[test]
     fn test_rle() {
         let s = include_str!("../patterns/blinkerpuffer2.rle");
         let p = Field::from_rle(s);
-        let ss = format!("{}", Game::new(p, AutomataRule::cgol()).to_string());
+        let ss = Game::new(p, AutomataRule::cgol()).to_string();
 
-        assert_eq!("\u{1b}[1;1H\u{1b}[38;5;15m▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬\n             \u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█ \n            \u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\n           \u{1b}[38;5;34m█\u{1b}[38;5;34m█ \u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\n            \u{1b}[38;5;34m█\u{1b}[38;5;34m█   \n                 \n                 \n         \u{1b}[38;5;34m█ \u{1b}[38;5;34m█     \n  \u{1b}[38;5;34m█     \u{1b}[38;5;34m█  \u{1b}[38;5;34m█     \n \u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█   \u{1b}[38;5;34m█ \u{1b}[38;5;34m█     \n\u{1b}[38;5;34m█\u{1b}[38;5;34m█   \u{1b}[38;5;34m█\u{1b}[38;5;34m█ \u{1b}[38;5;34m█\u{1b}[38;5;34m█       \n \u{1b}[38;5;34m█       \u{1b}[38;5;34m█       \n  \u{1b}[38;5;34m█\u{1b}[38;5;34m█  \u{1b}[38;5;34m█  \u{1b}[38;5;34m█       \n          \u{1b}[38;5;34m█      \n  \u{1b}[38;5;34m█\u{1b}[38;5;34m█  \u{1b}[38;5;34m█  \u{1b}[38;5;34m█       \n \u{1b}[38;5;34m█       \u{1b}[38;5;34m█       \n\u{1b}[38;5;34m█\u{1b}[38;5;34m█   \u{1b}[38;5;34m█\u{1b}[38;5;34m█ \u{1b}[38;5;34m█\u{1b}[38;5;34m█       \n \u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█   \u{1b}[38;5;34m█ \u{1b}[38;5;34m█     \n  \u{1b}[38;5;34m█     \u{1b}[38;5;34m█  \u{1b}[38;5;34m█     \n         \u{1b}[38;5;34m█ \u{1b}[38;5;34m█     \n                 \n                 \n            \u{1b}[38;5;34m█\u{1b}[38;5;34m█   \n           \u{1b}[38;5;34m█\u{1b}[38;5;34m█ \u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\n            \u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█\n             \u{1b}[38;5;34m█\u{1b}[38;5;34m█\u{1b}[38;5;34m█ \n\u{1b}[38;5;15m▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬\n0", ss);
+        assert_eq!("\u{1b}[1;1H\u{1b}[38;5;15m▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬\n             \u{1b}[38;5;34m███ \n            █████\n           ██ ███\n            ██   \n                 \n                 \n         █ █     \n  █     █  █     \n █████   █ █     \n██   ██ ██       \n █       █       \n  ██  █  █       \n          █      \n  ██  █  █       \n █       █       \n██   ██ ██       \n █████   █ █     \n  █     █  █     \n         █ █     \n                 \n                 \n            ██   \n           ██ ███\n            █████\n             ███ \n\u{1b}[38;5;15m▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬▬\n0", ss);
+    }
+
+    #[test]
+    fn test_orientations() {
+        let glider = Field::from_cells(".O.\n..O\nOOO");
+        let orientations = glider.orientations();
+        assert_eq!(8, orientations.len());
+        assert!(orientations[1] == Field::from_cells(".O.\nO..\nOOO"));
+        assert!(orientations[4] == Field::from_cells("..O\nO.O\n.OO"));
+
+        assert_eq!(1, Field::from_cells("OO\nOO").orientations().len());
+        let blinker = Field::from_cells("OOO").orientations();
+        assert_eq!(vec![(1, 3), (3, 1)], blinker.iter().map(|f| (f.rows, f.columns)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_phases() {
+        let glider = Field::from_file("patterns/glider.cells").unwrap();
+        let phases = glider.phases(&AutomataRule::cgol(), 100);
+        assert_eq!(4, phases.len());
+        assert!(phases[1] == Field::from_cells(".....\n.O.O.\n..OO.\n..O..\n....."));
+        assert!(phases.iter().all(|phase| (phase.rows, phase.columns) == (5, 5)));
+
+        assert_eq!(2, Field::from_cells("OOO").phases(&AutomataRule::cgol(), 100).len());
+        assert_eq!(1, Field::from_cells("OO\nOO").phases(&AutomataRule::cgol(), 100).len());
+        // Dies out instead of recurring.
+        assert_eq!(1, Field::from_cells("OO").phases(&AutomataRule::cgol(), 100).len());
+    }
+
+    #[test]
+    fn test_dont_care() {
+        let pattern = Field::pattern_from_cells("!Name: corner\nO?\n.O");
+        assert_eq!(vec![Some(true), None, Some(false), Some(true)], pattern.cells);
+        assert!(Field::from_cells("O?\n.O") == Field::from_cells("O.\n.O"));
+
+        let rle = Field::pattern_from_rle("x = 3, y = 2, rule = B3/S23\no?o$3?!");
+        assert_eq!(vec![Some(true), None, Some(true), None, None, None], rle.cells);
+
+        let field = Field::from_cells("OO..\n.O..\n..O.\n...O").with_topology(Topology::Plane);
+        assert_eq!(vec![(0, 0), (1, 1), (2, 2)], field.find_pattern(&pattern, false));
+
+        // Isolated matches need dead cells around them, the edges of the plane count as such.
+        let field = Field::from_cells("O...\n....\n..OO\n....").with_topology(Topology::Plane);
+        assert_eq!(vec![(0, 0), (2, 2), (2, 3)], field.find_pattern(&Field::pattern_from_cells("O"), false));
+        assert_eq!(vec![(0, 0)], field.find_pattern(&Field::pattern_from_cells("O"), true));
+        assert_eq!(vec![(0, 0), (2, 2)], field.find_pattern(&Field::pattern_from_cells("O?"), true));
+    }
+
+    #[test]
+    fn test_to_rle() {
+        let glider = Field::from_rle("x=5,y=5,rule=B3/S23\n$2bo$3bo$b3o2$!");
+        assert_eq!("x = 5, y = 5, rule = B3/S23\n$2bo$3bo$b3o!\n", glider.to_rle("B3/S23"));
+        assert_eq!("x = 3, y = 2, rule = B3/S23\n!\n", Field::with_size(2, 3).to_rle("B3/S23"));
+
+        let s = include_str!("../patterns/breeder1.rle");
+        let breeder = Field::from_rle(s);
+        let rle = breeder.to_rle("B3/S23");
+        assert!(rle.lines().all(|l| l.len() <= 70));
+        assert!(rle.trim_end().ends_with('!'));
+        assert!(breeder == Field::from_rle(&rle));
+
+        for &(rows, columns) in [(1, 1), (7, 100), (40, 3)].iter() {
+            let field = Field::from_random(rows, columns);
+            assert!(field == Field::from_rle(&field.to_rle("B36/S23")));
+            assert!(field == Field::from_rle(&field.to_rle("2-a3/23/4")));
+        }
+    }
+
+    #[test]
+    fn test_to_cells() {
+        let glider = Field::from_cells(".O.\n..O\nOOO");
+        assert_eq!(".O.\n..O\nOOO\n", glider.to_cells());
+
+        let field = Field::from_random(9, 33);
+        assert!(field == Field::from_cells(&field.to_cells()));
+    }
+
+    #[test]
+    fn test_writable_extension() {
+        assert_eq!("rle", writable_extension("dir.d/cgol.rle").unwrap());
+        assert_eq!("cells", writable_extension("cgol.cells").unwrap());
+        assert!(writable_extension("cgol").is_err());
+        assert!(writable_extension("cgol.png").is_err());
+        assert!(Field::with_size(1, 1).to_file("cgol.txt", "B3/S23").is_err());
     }
 
     #[test]
     fn test_neighbours() {
         {
             let r: Vec<&[bool]> = vec!(&[true, true, true], &[false, false, false], &[true, true, true]);
-            let n = neighbours(&r, 1, 1);
+            let n = neighbours(&r, 1, 1, Topology::Torus, &MOORE);
             assert_eq!(6, n);
         }
         {
             let r: Vec<&[bool]> = vec!(&[true, false, true], &[false, true, false], &[true, false, true]);
-            let n = neighbours(&r, 1, 1);
+            let n = neighbours(&r, 1, 1, Topology::Torus, &MOORE);
             assert_eq!(4, n);
         }
         {
             let r: Vec<&[bool]> = vec!(&[false, false, false], &[false, true, false], &[false, false, false]);
-            let n = neighbours(&r, 1, 1);
+            let n = neighbours(&r, 1, 1, Topology::Torus, &MOORE);
             assert_eq!(0, n);
         }
         {
             let r: Vec<&[bool]> = vec!(&[true, false, false], &[true, false, false], &[true, false, false]);
-            let n = neighbours(&r, 1, 1);
+            let n = neighbours(&r, 1, 1, Topology::Torus, &MOORE);
             assert_eq!(3, n);
         }
         {
             let r: Vec<&[bool]> = vec!(&[true, true, true], &[true, false, true], &[false, true, false]);
-            let n = neighbours(&r, 0, 0);
+            let n = neighbours(&r, 0, 0, Topology::Torus, &MOORE);
             assert_eq!(5, n);
         }
         {
             let r: Vec<&[bool]> = vec!(&[true, true, false], &[true, false, false], &[false, false, false]);
-            let n = neighbours(&r, 2, 2);
+            let n = neighbours(&r, 2, 2, Topology::Torus, &MOORE);
             assert_eq!(3, n);
         }
         {
             let r: Vec<&[bool]> = vec!(&[true, true, false], &[false, false, false], &[false, false, false], &[true, true, true]);
-            let n = neighbours(&r, 0, 0);
+            let n = neighbours(&r, 0, 0, Topology::Torus, &MOORE);
             assert_eq!(4, n);
         }
+        {
+            let r: Vec<&[bool]> = vec!(&[true, true, true], &[true, false, true], &[false, true, false]);
+            let n = neighbours(&r, 0, 0, Topology::Plane, &MOORE);
+            assert_eq!(2, n);
+        }
+        {
+            let r: Vec<&[bool]> = vec!(&[true, true, false], &[true, false, false], &[false, false, false]);
+            let n = neighbours(&r, 2, 2, Topology::Plane, &MOORE);
+            assert_eq!(0, n);
+        }
+        {
+            let r: Vec<&[bool]> = vec!(&[true, true, true], &[false, false, true], &[true, false, true]);
+            assert_eq!(2, neighbours(&r, 1, 1, Topology::Torus, &VON_NEUMANN));
+            assert_eq!(4, neighbours(&r, 1, 1, Topology::Torus, &HEXAGONAL));
+        }
+    }
+
+    #[test]
+    fn test_resized() {
+        let field = Field::from_cells("\
+O..
+.O.
+..O");
+        let larger = field.resized(5, 7);
+        assert!(larger == Field::from_cells("\
+.......
+..O....
+...O...
+....O..
+......."));
+        assert!(larger.resized(3, 3) == field);
+        assert!(field.resized(1, 2) == Field::from_cells(".O"));
     }
 
     #[test]
@@ -315,6 +673,146 @@ O...OO");
         assert_eq!(expected.cells, empty.cells);
     }
 
+    #[test]
+    fn test_glider_leaves_plane() {
+        let glider = Field::from_cells("\
+.O.....
+..O....
+OOO....
+.......
+.......
+.......").with_topology(Topology::Plane);
+
+        let mut field = glider.clone();
+        for _ in 0..40 {
+            field.cells = field.next_generation(&AutomataRule::cgol(), 1);
+        }
+
+        // The glider runs into the bottom edge and settles into a block instead of wrapping around.
+        let expected = Field::from_cells("\
+.......
+.......
+.......
+.......
+....OO.
+....OO.");
+        assert!(expected == field);
+    }
+
+    #[test]
+    fn test_glider_through_klein_edge() {
+        let glider = Field::from_cells("\
+.........
+.........
+.........
+.........
+.........
+.........
+....O....
+.....O...
+...OOO...").with_topology(Topology::Klein);
+
+        let mut field = glider.clone();
+        for _ in 0..12 {
+            field.cells = field.next_generation(&AutomataRule::cgol(), 1);
+        }
+
+        // Crossing the bottom edge mirrors the glider, which now travels down and to the left.
+        let expected = Field::from_cells("\
+.O.......
+O........
+OOO......
+.........
+.........
+.........
+.........
+.........
+.........");
+        assert!(expected == field);
+
+        // A Klein bottle is the top half of a torus twice as high whose bottom half is mirrored.
+        let mut cover = Field::with_size(18, 9);
+        cover.insert(glider.clone(), 0, 0);
+        let mirrored = glider.proj2d().iter().flat_map(|row| row.iter().rev().copied()).collect();
+        cover.insert(Field::new(mirrored, 9, 9), 9, 0);
+        for _ in 0..12 {
+            cover.cells = cover.next_generation(&AutomataRule::cgol(), 1);
+        }
+        assert_eq!(&cover.cells[..81], &field.cells[..]);
+    }
+
+    #[test]
+    fn test_next_generation_parallel() {
+        let rule = AutomataRule::cgol();
+        for &(rows, columns) in [(1, 1), (3, 7), (40, 80), (97, 13)].iter() {
+            let field = Field::from_random(rows, columns);
+            let p = field.proj2d();
+            let expected = field.apply_rule(field.calculate_neighbours(&p, rule.neighbourhood()), &rule);
+
+            for threads in [2, 3, 8, 200].iter() {
+                assert_eq!(expected, field.next_generation(&rule, *threads), "{}x{} on {} threads", rows, columns, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_generation_non_totalistic() {
+        let rule = AutomataRule::from("B2-a/S12");
+        let field = Field::from_random(30, 41);
+        let expected = field.next_generation(&rule, 1);
+        for threads in [2, 7].iter() {
+            assert_eq!(expected, field.next_generation(&rule, *threads));
+        }
+
+        // Written as a non-totalistic rule with every letter, Life must behave like Life.
+        let life = AutomataRule::from("B3cekainyqjr/S2cekain3");
+        let cgol = AutomataRule::cgol();
+        assert_eq!(field.next_generation(&cgol, 1), field.next_generation(&life, 1));
+    }
+
+    #[test]
+    fn test_configuration() {
+        let r: Vec<&[bool]> = vec!(&[true, true, false], &[false, false, false], &[false, false, true]);
+        assert_eq!(0b1000_1001, configuration(&r, 1, 1, Topology::Torus));
+        assert_eq!(0b0000_0011, configuration(&r, 0, 1, Topology::Plane));
+    }
+
+    #[test]
+    fn test_next_states_brians_brain() {
+        let rule = AutomataRule::from("/2/3");
+        let field = Field::from_cells("\
+......
+..OO..
+......
+......");
+        let mut states = Field::from_alive(&field);
+
+        let expected = [
+            // Both cells start dying, cells with exactly two live neighbours are born.
+            "..OO..|..22..|..OO..|......|",
+            // Dying cells do not count as neighbours, so the cells beside them are born.
+            "..22..|.O..O.|..22..|.O..O.|",
+        ];
+        for step in expected.iter() {
+            let alive = states.cells.iter().map(|&s| s == 1).collect();
+            let next = Field::new(alive, states.rows, states.columns);
+            states.cells = states.next_states(&next.next_generation(&rule, 1), &Rule::from(rule.clone()));
+
+            let render = states.proj2d().iter()
+                .map(|row| row.iter().map(|&s| match s { 0 => '.', 1 => 'O', _ => '2' }).collect::<String>() + "|")
+                .collect::<String>();
+            assert_eq!(*step, render);
+        }
+    }
+
+    #[test]
+    fn test_band_rows() {
+        assert_eq!(10, band_rows(10, 1));
+        assert_eq!(4, band_rows(10, 3));
+        assert_eq!(1, band_rows(3, 8));
+        assert_eq!(5, band_rows(5, 0));
+    }
+
     /*
     use std::time::Instant;
 
@@ -330,17 +828,17 @@ O...OO");
         });
 
         let t1 = benchmark(10, || {
-            field.calculate_neighbours(&p);
+            field.calculate_neighbours(&p, rule.neighbourhood());
         });
 
         let t2 = benchmark(10, || {
-            let n = field.calculate_neighbours(&p);
+            let n = field.calculate_neighbours(&p, rule.neighbourhood());
             field.apply_rule(n, &rule);
         }) - t1;
 
         let t3 = benchmark(10, || {
             let p = Field::from_random(10, 10);
-            field.find_pattern(&p);
+            field.find_pattern(&p.map(Some), false);
         });
 
         println!("Field{{{},{}}}", r, c);
@@ -1,127 +1,604 @@
-use crate::field::{Field, wrap};
-use crate::rule::AutomataRule;
-use crate::term::{colormap_gb, gfx_cell, gfx_cell_highres, gfx_hline, gfx_hline_highres, gfx_pos1};
+use std::cell::OnceCell;
+use std::fmt;
+
+use crate::bitfield::BitField;
+use crate::cycle::CycleDetector;
+use crate::field::{Field, Pattern};
+use crate::hashlife::Universe;
+use crate::history::History;
+use crate::image::Image;
+use crate::palette::Palette;
+use crate::plane::Plane;
+use crate::rule::{AutomataRule, Rule};
+use crate::screen::Frame;
+use crate::spaceship::{ShipDetector, Spaceship};
+use crate::term::{gfx_cell, gfx_cell_braille, gfx_cell_highres, gfx_density, gfx_hline, gfx_pos1};
+use crate::viewport::Viewport;
+
+/// Number of recent generations a repetition is looked for in, i.e. the longest period detected.
+const CYCLE_WINDOW: usize = 1000;
+/// Number of recent generations a moved object is looked for in, i.e. the longest spaceship period detected.
+const SHIP_WINDOW: usize = 100;
+/// Longest period of marked patterns whose phases are all marked.
+const PATTERN_PERIOD: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Engine {
+    /// Counts the neighbours of every cell one by one.
+    Naive,
+    /// Steps 64 cells at once on a bit-packed copy of the field.
+    Packed,
+    /// Advances 2^k generations per iteration on an unbounded quadtree; the field is a window onto it.
+    HashLife(u8),
+    /// Steps bit-packed chunks of an unbounded plane; the field is a window onto it.
+    Sparse,
+}
+
+impl Engine {
+    pub fn generations(&self) -> usize {
+        match self {
+            Engine::HashLife(k) => 1 << k,
+            _ => 1,
+        }
+    }
+}
+
+/// The cells of the current generation. The packed engine keeps them in `bits` across generations,
+/// they are only unpacked when asked for, e.g. to be drawn.
+struct Cells {
+    bits: Option<BitField>,
+    field: OnceCell<Field<bool>>,
+    states: OnceCell<Field<u8>>,
+}
+
+impl Cells {
+    fn new(states: Field<u8>) -> Cells {
+        let field = Field::new(states.cells.iter().map(|&state| state == 1).collect(), states.rows, states.columns)
+            .with_topology(states.topology);
+        Cells { bits: None, field: OnceCell::from(field), states: OnceCell::from(states) }
+    }
+
+    fn packed(bits: BitField) -> Cells {
+        Cells { bits: Some(bits), field: OnceCell::new(), states: OnceCell::new() }
+    }
+
+    fn field(&self) -> &Field<bool> {
+        self.field.get_or_init(|| Field::from(self.bits.as_ref().expect("cells neither unpacked nor packed")))
+    }
+
+    /// Only two-state rules are packed, so the states follow from the live cells.
+    fn states(&self) -> &Field<u8> {
+        self.states.get_or_init(|| Field::from_alive(self.field()))
+    }
+
+    fn set(&mut self, row: usize, column: usize, alive: bool) {
+        if let Some(field) = self.field.get_mut() {
+            field.cells[row * field.columns + column] = alive;
+        }
+        if let Some(states) = self.states.get_mut() {
+            states.cells[row * states.columns + column] = alive as u8;
+        }
+        if let Some(bits) = self.bits.as_mut() {
+            bits.set(row, column, alive);
+        }
+    }
+}
 
 pub struct Game {
-    field: Field<bool>,
-    ages: Field<u32>,
+    cells: Cells,
+    /// Generation each live cell was born in, which its age is counted from.
+    born: Field<usize>,
     marked: Field<bool>,
-    rule: AutomataRule,
+    rule: Rule,
+    engine: Engine,
+    universe: Option<Universe>,
+    plane: Option<Plane>,
+    /// Plane coordinates `(top, left)` of the field's top left cell under the unbounded engines.
+    origin: (i64, i64),
+    threads: usize,
+    history: History,
+    palette: Palette,
     iterations: usize,
+    /// Cells born and died in the last iteration.
+    births: usize,
+    deaths: usize,
+    /// Occurrences of the pattern last marked.
+    matches: Option<usize>,
+    /// The pattern last marked with its rotations, reflections and phases.
+    variants: Option<(Pattern, Vec<Pattern>)>,
+    /// Whether marked patterns need a dead border around them.
+    isolated: bool,
+    cycles: CycleDetector,
+    /// Generations after which the field repeats itself, once it does.
+    period: Option<usize>,
+    /// Only set when spaceships are looked for, as finding the objects takes longer than a generation.
+    spaceships: Option<ShipDetector>,
+    /// Objects that reappeared elsewhere in the last iteration.
+    ships: Vec<Spaceship>,
+    /// Whether the game can't go on, see `next_iteration`.
+    halted: bool,
 }
 
 impl Game {
-    pub fn new(field: Field<bool>, rule: AutomataRule) -> Self {
-        let ages = Field::with_size(field.rows, field.columns);
+    pub fn new(field: Field<bool>, rule: impl Into<Rule>) -> Self {
+        let born = Field::with_size(field.rows, field.columns);
         let marked = Field::with_size(field.rows, field.columns);
+        let cells = Cells::new(Field::from_alive(&field));
         let iterations = 0;
-        Game { field, ages, marked, rule, iterations }
+        let mut game = Game {
+            cells, born, marked, rule: rule.into(), engine: Engine::Packed, universe: None, plane: None, origin: (0, 0), threads: 1,
+            history: History::new(0), palette: Palette::classic(), iterations, births: 0, deaths: 0, matches: None, variants: None, isolated: false,
+            cycles: CycleDetector::new(CYCLE_WINDOW), period: None, spaceships: None, ships: Vec::new(), halted: false,
+        };
+        game.restart_engine();
+        game.reset_cycles();
+        game
+    }
+
+    pub fn with_engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self.restart_engine();
+        self.reset_cycles();
+        self
+    }
+
+    /// Keeps up to `limit` previous generations to rewind to.
+    pub fn with_history(mut self, limit: usize) -> Self {
+        self.history = History::new(limit);
+        self
+    }
+
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Only marks patterns with nothing but dead cells right around them.
+    pub fn with_isolated_marks(mut self, isolated: bool) -> Self {
+        self.isolated = isolated;
+        self
+    }
+
+    /// Looks for spaceships in every generation, see `ships`.
+    pub fn with_ship_detection(mut self, detect: bool) -> Self {
+        self.spaceships = if detect { Some(ShipDetector::new(SHIP_WINDOW)) } else { None };
+        self.reset_cycles();
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
     }
 
     pub async fn next_iteration(&mut self) {
-        let cells_2d = self.field.proj2d();
-        let neighbours = self.field.calculate_neighbours(&cells_2d);
-        let new_cells = self.field.apply_rule(neighbours, &self.rule);
-        let ages = self.calculate_ages(&new_cells);
-
-        self.marked = Field::with_size(self.field.rows, self.field.columns);
-        self.field = Field::new(new_cells, self.field.rows, self.field.columns);
-        self.ages = Field::new(ages, self.field.rows, self.field.columns);
-        self.iterations += 1;
-    }
-
-    pub fn mark_pattern(&mut self, pattern: &Field<bool>) {
-        let matches = self.field.find_pattern(pattern);
-
-        for (r, c) in matches {
-            for rr in 0..pattern.rows {
-                for cc in 0..pattern.columns {
-                    let rrr = wrap(r, rr as i32, self.field.rows);
-                    let ccc = wrap(c, cc as i32, self.field.columns);
-                    let idx = rrr * self.field.columns + ccc;
-                    self.marked.cells[idx] = self.field.cells[idx] & true;
+        // The game stops as it is once the generations can't be counted or the universe can't grow any further.
+        let iterations = match self.iterations.checked_add(self.engine.generations()) {
+            Some(iterations) if self.step_universe() => iterations,
+            _ => {
+                self.halted = true;
+                return;
+            }
+        };
+        if self.history.is_enabled() {
+            match self.cells.bits.as_ref() {
+                Some(bits) => self.history.push_packed(bits, &self.born.cells, self.iterations),
+                None => self.history.push(&self.cells.states().cells, &self.born.cells, self.iterations),
+            }
+        }
+
+        match self.cells.bits.as_ref() {
+            // Only the cells born are looked at, the others stay packed until they are asked for.
+            Some(bits) => {
+                let next = bits.step(self.automata_rule(), self.threads);
+                let born = bits.born(&next);
+                self.births = born.len();
+                self.deaths = bits.population() + born.len() - next.population();
+                for idx in born {
+                    self.born.cells[idx] = iterations;
                 }
+                self.cells = Cells::packed(next);
             }
+            None => {
+                let field = self.cells.field();
+                let new_cells = match (&self.rule, self.engine) {
+                    // Larger than Life rules count on the whole field, under the naive and packed engines as the unbounded ones don't take them.
+                    (Rule::Ltl(ltl), _) => ltl.next_generation(field, self.threads),
+                    // Mirrored edges and non-totalistic rules are not bit-packable, so they use the cell-by-cell path.
+                    (Rule::Automata(rule), Engine::Packed) if !field.topology.is_twisted() && rule.is_outer_totalistic() => {
+                        BitField::from(field).step(rule, self.threads).to_cells()
+                    }
+                    (Rule::Automata(rule), Engine::Naive | Engine::Packed) => field.next_generation(rule, self.threads),
+                    (_, Engine::HashLife(_)) => {
+                        let universe = self.universe.as_ref().expect("hashlife engine without universe");
+                        universe.to_field(self.origin.0, self.origin.1, field.rows, field.columns).cells
+                    }
+                    (_, Engine::Sparse) => {
+                        let plane = self.plane.as_mut().expect("sparse engine without plane");
+                        plane.step(self.threads);
+                        plane.to_field(self.origin.0, self.origin.1, field.rows, field.columns).cells
+                    }
+                };
+                let states = self.cells.states().next_states(&new_cells, &self.rule);
+                let next = Cells::new(Field::new(states, field.rows, field.columns).with_topology(field.topology));
+
+                let changes = field.cells.iter().zip(&next.field().cells).enumerate().filter(|(_, (old, new))| old != new);
+                let (mut births, mut deaths) = (0, 0);
+                for (idx, (_, &alive)) in changes {
+                    if alive {
+                        births += 1;
+                        self.born.cells[idx] = iterations;
+                    } else {
+                        deaths += 1;
+                    }
+                }
+                self.births = births;
+                self.deaths = deaths;
+                self.cells = next;
+            }
+        }
+        if self.variants.is_some() {
+            self.marked.cells.fill(false);
+        }
+        self.iterations = iterations;
+        // Once periodic the field stays so, which spares looking for the exact period more than once.
+        self.period = match self.push_cycles() {
+            Some(found) => Some(self.period.unwrap_or_else(|| self.exact_period(found))),
+            None => None,
+        };
+        if let Some(spaceships) = self.spaceships.as_mut() {
+            self.ships = spaceships.push(self.cells.states(), self.rule.reach(), self.iterations);
         }
     }
 
-    pub fn calculate_ages(&self, new_cells: &[bool]) -> Vec<u32> {
-        self.field.cells.iter().zip(new_cells).enumerate().map(|(idx, state)| match state {
-            (true, true) => self.ages.cells[idx] + 1,
-            (_, _) => 0,
-        }).collect()
+    /// Goes back to the previous generation, returning `false` if there is none left.
+    /// The unbounded engines restart from the field, forgetting whatever lies outside of it.
+    pub fn rewind(&mut self) -> bool {
+        let (states, born, iterations) = match self.history.pop() {
+            Some(generation) => generation,
+            None => return false,
+        };
+
+        let field = self.cells.field();
+        self.cells = Cells::new(Field::new(states, field.rows, field.columns).with_topology(field.topology));
+        self.born.cells = born;
+        self.marked.cells.fill(false);
+        self.iterations = iterations;
+        self.births = 0;
+        self.deaths = 0;
+        self.restart_engine();
+        self.reset_cycles();
+        true
     }
 
-    pub fn to_string(&self) -> String {
-        let mut output = String::new();
-        let hline = gfx_hline(self.field.columns);
-        output += gfx_pos1();
-        output += hline.as_str();
-        output += "\n";
+    /// Resizes the field with its cells centred, forgetting the history of the old size.
+    pub fn resize(&mut self, rows: usize, columns: usize) {
+        self.cells = Cells::new(self.cells.states().resized(rows, columns));
+        self.born = self.born.resized(rows, columns);
+        self.marked = Field::with_size(rows, columns);
+        self.history.clear();
+        self.restart_engine();
+        self.reset_cycles();
+    }
 
-        let mut current_color = String::new();
-        //output += "\x1B[38;5;1m  012345678901234567890\n";
-        for r in 0..self.field.rows {
-            //output += format!("{:0w$}", r.to_string(), w=2).as_str();
-            for c in 0..self.field.columns {
-                let idx = r * self.field.columns + c;
+    pub fn field(&self) -> &Field<bool> {
+        self.cells.field()
+    }
 
-                let alive = self.field.cells[idx];
-                let gfx = gfx_cell(alive);
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
 
-                let age = self.ages.cells[idx];
-                let color = if self.marked.cells[idx] { "\x1B[38;5;1m".to_string() } else { colormap_gb(age) };
+    /// Whether the last iteration couldn't be computed, leaving the game where it was.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
 
-                if gfx != " " && color != current_color {
-                    output += color.as_str();
-                    current_color = color;
+    /// Generations advanced per iteration.
+    pub fn generations(&self) -> usize {
+        self.engine.generations()
+    }
+
+    pub fn population(&self) -> usize {
+        match &self.cells.bits {
+            Some(bits) => bits.population(),
+            None => self.cells.field().cells.iter().filter(|&&alive| alive).count(),
+        }
+    }
+
+    /// Cells born and died in the last iteration.
+    pub fn births_deaths(&self) -> (usize, usize) {
+        (self.births, self.deaths)
+    }
+
+    pub fn matches(&self) -> Option<usize> {
+        self.matches
+    }
+
+    /// Generations after which the whole field repeats itself: 1 for still lifes, more for oscillators.
+    pub fn period(&self) -> Option<usize> {
+        self.period
+    }
+
+    /// Objects that recur shifted, with their velocity, if spaceships are looked for.
+    pub fn ships(&self) -> &[Spaceship] {
+        &self.ships
+    }
+
+    /// Sets a single cell, e.g. from the editor, in the field and the engine's own world.
+    pub fn set_cell(&mut self, row: usize, column: usize, alive: bool) {
+        self.cells.set(row, column, alive);
+        self.born.cells[row * self.born.columns + column] = self.iterations;
+
+        let (top, left) = self.origin;
+        if let Some(universe) = self.universe.as_mut() {
+            universe.set(left + column as i64, top + row as i64, alive);
+        }
+        if let Some(plane) = self.plane.as_mut() {
+            plane.set(left + column as i64, top + row as i64, alive);
+        }
+        self.reset_cycles();
+    }
+
+    /// Moves the field of the unbounded engines to show their plane from (`top`, `left`) on,
+    /// e.g. when panning. Cells that stay in view keep their age.
+    pub fn move_to(&mut self, top: i64, left: i64) {
+        let field = self.cells.field();
+        let (rows, columns) = (field.rows, field.columns);
+        let window = match (&self.universe, &self.plane) {
+            (Some(universe), _) => universe.to_field(top, left, rows, columns),
+            (_, Some(plane)) => plane.to_field(top, left, rows, columns),
+            _ => return,
+        };
+        let (dr, dc) = (top - self.origin.0, left - self.origin.1);
+        let born = (0..rows * columns).map(|i| {
+            let (r, c) = ((i / columns) as i64 + dr, (i % columns) as i64 + dc);
+            match r >= 0 && r < rows as i64 && c >= 0 && c < columns as i64 {
+                true => self.born.cells[r as usize * columns + c as usize],
+                false => self.iterations,
+            }
+        }).collect();
+
+        self.cells = Cells::new(Field::from_alive(&window.with_topology(field.topology)));
+        self.born.cells = born;
+        self.origin = (top, left);
+        self.marked.cells.fill(false);
+        self.history.clear();
+        self.reset_cycles();
+    }
+
+    /// Sets the engine up from the field, e.g. after it was rewound. The unbounded engines forget
+    /// whatever lies outside of it, and the packed one keeps the cells packed if the rule allows.
+    fn restart_engine(&mut self) {
+        let field = self.cells.field();
+        let (top, left) = self.origin;
+        self.universe = match self.engine {
+            Engine::HashLife(_) => Some(Universe::from_field(field, top, left, self.automata_rule().clone())),
+            _ => None,
+        };
+        self.plane = match self.engine {
+            Engine::Sparse => Some(Plane::from_field(field, top, left, self.automata_rule().clone())),
+            _ => None,
+        };
+        let packable = self.engine == Engine::Packed && !field.topology.is_twisted()
+            && matches!(&self.rule, Rule::Automata(rule) if rule.is_outer_totalistic() && rule.states() == 2);
+        let bits = if packable { Some(BitField::from(field)) } else { None };
+        self.cells.states();
+        self.cells.bits = bits;
+        // A fresh universe has room to grow again.
+        self.halted = false;
+    }
+
+    /// Starts looking for cycles and spaceships from the current generation on.
+    fn reset_cycles(&mut self) {
+        self.cycles.clear();
+        self.push_cycles();
+        self.period = None;
+        self.ships.clear();
+        if let Some(spaceships) = self.spaceships.as_mut() {
+            spaceships.clear();
+            self.ships = spaceships.push(self.cells.states(), self.rule.reach(), self.iterations);
+        }
+    }
+
+    /// Advances the hashlife universe ahead of the field, `false` if it can't grow any further.
+    fn step_universe(&mut self) -> bool {
+        match (self.engine, self.universe.as_mut()) {
+            (Engine::HashLife(k), Some(universe)) => universe.step(k),
+            _ => true,
+        }
+    }
+
+    /// The shortest period of the field that divides `found`. HashLife only shows every 2^k-th
+    /// generation, so the divisors of what the cycle detector found are checked on a copy of its
+    /// universe, stepped a generation at a time where needed.
+    fn exact_period(&self, found: usize) -> usize {
+        let universe = match (&self.universe, self.engine.generations()) {
+            (Some(universe), generations) if generations > 1 => universe,
+            _ => return found,
+        };
+        let field = self.cells.field();
+        let (top, left) = self.origin;
+
+        let mut copy = universe.clone();
+        let mut stepped = 0;
+        for period in (1..found).filter(|&d| found.is_multiple_of(d)) {
+            let steps = period - stepped;
+            for bit in (0..usize::BITS).filter(|&bit| steps >> bit & 1 == 1) {
+                if !copy.step(bit as u8) {
+                    return found;
                 }
-                output += gfx;
             }
-            output += "\n";
+            stepped = period;
+            if copy.to_field(top, left, field.rows, field.columns).cells == field.cells {
+                return period;
+            }
         }
-        output += hline.as_str();
-        output += "\n";
-        output += self.iterations.to_string().as_str();
-        output
+        found
+    }
+
+    /// Records the current generation for cycle detection, as packed words if the engine keeps it so.
+    fn push_cycles(&mut self) -> Option<usize> {
+        match &self.cells.bits {
+            Some(bits) => self.cycles.push(bits.words(), self.iterations),
+            None => self.cycles.push(&self.cells.states().cells, self.iterations),
+        }
+    }
+
+    /// Marks the live cells of the pattern wherever it occurs in any rotation, reflection or phase.
+    /// Patterns with don't care cells and Generations or LtL rules are only rotated and reflected.
+    pub fn mark_pattern(&mut self, pattern: &Pattern) {
+        if self.variants.as_ref().is_none_or(|(marked, _)| marked != pattern) {
+            let phases = match &self.rule {
+                Rule::Automata(rule) if rule.states() == 2 && pattern.cells.iter().all(Option::is_some) => pattern.map(|cell| cell == Some(true))
+                    .phases(rule, PATTERN_PERIOD).iter()
+                    .map(|phase| phase.map(Some))
+                    .collect(),
+                _ => vec![pattern.clone()],
+            };
+            let mut variants: Vec<Pattern> = Vec::new();
+            for variant in phases.iter().flat_map(Field::orientations) {
+                if !variants.contains(&variant) {
+                    variants.push(variant);
+                }
+            }
+            self.variants = Some((pattern.clone(), variants));
+        }
+
+        let field = self.cells.field();
+        let mut matches = 0;
+        for variant in self.variants.as_ref().map_or(&[][..], |(_, variants)| variants) {
+            for (r, c) in field.find_pattern(variant, self.isolated) {
+                matches += 1;
+                for rr in 0..variant.rows {
+                    for cc in 0..variant.columns {
+                        let delta = (rr as i32, cc as i32);
+                        let alive = variant.cells[rr * variant.columns + cc] == Some(true);
+                        if let Some((rrr, ccc)) = field.topology.offset((r, c), delta, field.rows, field.columns).filter(|_| alive) {
+                            self.marked.cells[rrr * field.columns + ccc] = true;
+                        }
+                    }
+                }
+            }
+        }
+        self.matches = Some(matches);
+    }
+
+    /// The rule of the bit-packed field and the unbounded engines. Panics for Larger than Life rules,
+    /// which only the naive and packed engines run on the whole field.
+    fn automata_rule(&self) -> &AutomataRule {
+        match &self.rule {
+            Rule::Automata(rule) => rule,
+            Rule::Ltl(_) => panic!("Larger than Life rules are not supported by the {:?} engine", self.engine),
+        }
+    }
+
+    /// Iterations the cell at `idx` has been alive for, 0 if it isn't.
+    fn age(&self, idx: usize) -> u32 {
+        match self.cells.states().cells[idx] {
+            1 => (self.iterations.saturating_sub(self.born.cells[idx]) / self.generations()) as u32,
+            _ => 0,
+        }
+    }
+
+    /// Paints the cells inside `viewport` as `pixels` x `pixels` squares coloured like the text output,
+    /// dead cells in black.
+    pub fn to_image(&self, viewport: &Viewport, pixels: usize) -> Image {
+        let (field, states) = (self.cells.field(), self.cells.states());
+        let (sr, sc) = viewport.scale();
+        let rows = field.rows.saturating_sub(viewport.top).min(viewport.rows * sr);
+        let columns = field.columns.saturating_sub(viewport.left).min(viewport.columns * sc);
+        let (width, height) = (columns * pixels, rows * pixels);
+
+        let colors = (0..rows).flat_map(|r| (0..columns).map(move |c| (viewport.top + r) * field.columns + viewport.left + c))
+            .map(|idx| match states.cells[idx] {
+                _ if self.marked.cells[idx] => self.palette.marked().rgb(),
+                0 => [0; 3],
+                1 => self.palette.age(self.age(idx)).rgb(),
+                state => self.palette.decay(state, self.rule.states()).rgb(),
+            })
+            .collect::<Vec<[u8; 3]>>();
+
+        let pixels = (0..height).flat_map(|y| (0..width).map(move |x| (y, x)))
+            .map(|(y, x)| colors[y / pixels * columns + x / pixels])
+            .collect();
+        Image { width, height, pixels }
     }
 
-    pub fn to_string_highres(&self) -> String {
+    /// Renders the part of the field inside `viewport`: one cell per character at 1:1, quadrants or
+    /// Braille patterns at 2:1 and shades for the share of live cells when zoomed out further.
+    pub fn to_frame(&self, viewport: &Viewport) -> Frame {
+        let (field, states) = (self.cells.field(), self.cells.states());
+        let (rows, columns) = viewport.visible(field.rows, field.columns);
+        let (sr, sc) = viewport.scale();
+        let index = |r: usize, c: usize| match r < field.rows && c < field.columns {
+            true => Some(r * field.columns + c),
+            false => None,
+        };
+        let state = |r, c| index(r, c).map_or(0, |i| states.cells[i]);
+
+        let mut cells = Vec::with_capacity(rows * columns);
+        for vr in 0..rows {
+            for vc in 0..columns {
+                let (r, c) = (viewport.top + vr * sr, viewport.left + vc * sc);
+                let block = (r..r + sr).flat_map(|rr| (c..c + sc).filter_map(move |cc| index(rr, cc))).collect::<Vec<_>>();
+
+                let gfx = match (sr, sc) {
+                    (1, 1) => gfx_cell(state(r, c) > 0).chars().next().unwrap(),
+                    (2, 2) => gfx_cell_highres(state(r, c) > 0, state(r, c + 1) > 0, state(r + 1, c) > 0, state(r + 1, c + 1) > 0).chars().next().unwrap(),
+                    (4, 2) => gfx_cell_braille([0, 1, 2, 3].map(|dr| [state(r + dr, c) > 0, state(r + dr, c + 1) > 0])),
+                    _ => gfx_density(block.iter().filter(|&&i| states.cells[i] > 0).count(), sr * sc).chars().next().unwrap(),
+                };
+
+                let age = block.iter().map(|&i| self.age(i)).sum::<u32>() / (sr * sc) as u32;
+                let dying = block.iter().all(|&i| states.cells[i] != 1);
+                let color = if block.iter().any(|&i| self.marked.cells[i]) {
+                    self.palette.marked()
+                } else if dying {
+                    self.palette.decay(block.iter().map(|&i| states.cells[i]).max().unwrap_or(0), self.rule.states())
+                } else {
+                    self.palette.age(age)
+                };
+                cells.push((gfx, self.palette.escape(color)));
+            }
+        }
+
+        Frame { rows, columns, cells, line: self.palette.escape(self.palette.line()), status: self.iterations.to_string() }
+    }
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (field, states) = (self.cells.field(), self.cells.states());
         let mut output = String::new();
-        let hline = gfx_hline_highres(self.field.columns);
+        let hline = self.palette.escape(self.palette.line()) + &gfx_hline(field.columns);
         output += gfx_pos1();
         output += hline.as_str();
         output += "\n";
 
         let mut current_color = String::new();
-        for r in (0..self.field.rows).step_by(2) {
-            for c in (0..self.field.columns).step_by(2) {
-                let index = |r, c| r * self.field.columns + c;
-                let idxul = index(r, c);
-                let idxur = idxul + 1;
-                let idxbl = index(r + 1, c);
-                let idxbr = idxbl + 1;
-
-                let alive_ul = *self.field.cells.get(idxul).unwrap();
-                let alive_ur = *self.field.cells.get(idxur).unwrap_or(&false);
-                let alive_bl = *self.field.cells.get(idxbl).unwrap_or(&false);
-                let alive_br = *self.field.cells.get(idxbr).unwrap_or(&false);
-
-                let gfx = gfx_cell_highres(alive_ul, alive_ur, alive_bl, alive_br);
+        //output += "\x1B[38;5;1m  012345678901234567890\n";
+        for r in 0..field.rows {
+            //output += format!("{:0w$}", r.to_string(), w=2).as_str();
+            for c in 0..field.columns {
+                let idx = r * field.columns + c;
 
-                let age_ul = *self.ages.cells.get(idxul).unwrap();
-                let age_ur = *self.ages.cells.get(idxur).unwrap_or(&0);
-                let age_bl = *self.ages.cells.get(idxbl).unwrap_or(&0);
-                let age_br = *self.ages.cells.get(idxbr).unwrap_or(&0);
-                let age = (age_ul + age_ur + age_bl + age_br) / 4;
+                let state = states.cells[idx];
+                let gfx = gfx_cell(state > 0);
 
-                let color = if self.marked.cells[idxul] { "\x1B[38;5;1m".to_string() } else { colormap_gb(age) };
+                let age = self.age(idx);
+                let color = self.palette.escape(if self.marked.cells[idx] {
+                    self.palette.marked()
+                } else if state > 1 {
+                    self.palette.decay(state, self.rule.states())
+                } else {
+                    self.palette.age(age)
+                });
 
                 if gfx != " " && color != current_color {
                     output += color.as_str();
                     current_color = color;
                 }
-
                 output += gfx;
             }
             output += "\n";
@@ -129,15 +606,20 @@ impl Game {
         output += hline.as_str();
         output += "\n";
         output += self.iterations.to_string().as_str();
-        output
+        f.write_str(output.as_str())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use futures::executor::block_on;
+
     use crate::field::Field;
-    use crate::game::Game;
+    use crate::game::{Engine, Game};
+    use crate::hashlife::MAX_EXPONENT;
     use crate::rule::AutomataRule;
+    use crate::palette::Palette;
+    use crate::viewport::Viewport;
 
     #[test]
     fn test_output_highres() {
@@ -149,14 +631,284 @@ mod tests {
 .OOO..");
 
             let game = Game::new(glider, AutomataRule::cgol());
-            println!("{}", game.to_string_highres());
+            println!("{}", game.to_frame(&Viewport::new(2, 3, 2)));
         }
     }
 
+    #[test]
+    fn test_output_viewport() {
+        let mut game = Game::new(Field::from_file("patterns/glidergun.cells").unwrap(), AutomataRule::cgol());
+        let (rows, columns) = (game.field().rows, game.field().columns);
+        block_on(game.next_iteration());
+
+        assert_eq!(game.to_string(), game.to_frame(&Viewport::new(rows, columns, 1)).to_string());
+        let glider = Game::new(Field::from_cells("..O.\n...O\n.OOO\n...."), AutomataRule::cgol());
+        let highres = glider.to_frame(&Viewport::new(rows, columns, 2)).to_string();
+        assert!(highres.contains("\u{259A}\n\u{259D}\u{2580}\n"), "{:?}", highres);
+        let braille = glider.to_frame(&Viewport::new(rows, columns, 2).with_braille()).to_string();
+        assert!(braille.contains("\u{2820}\u{2835}\n"), "{:?}", braille);
+
+        let block = Game::new(Field::from_cells("OOOO....\nOOOO....\n........\n......O."), AutomataRule::cgol());
+        let output = block.to_frame(&Viewport::new(5, 5, 4)).to_string();
+        assert!(output.contains("\u{2592}\u{2591}\n"), "{:?}", output);
+
+        let mut viewport = Viewport::new(1, 1, 1);
+        viewport.pan((1, 1), 4, 8);
+        viewport.pan((1, 1), 4, 8);
+        assert!(block.to_frame(&viewport).to_string().contains("\u{25AC}\n \n"));
+    }
+
+    #[test]
+    fn test_to_image() {
+        let mut game = Game::new(Field::from_cells(".O..\nOO..\n...."), AutomataRule::cgol());
+        block_on(game.next_iteration());
+        game.mark_pattern(&Field::pattern_from_cells("O"));
+        let (marked, dead) = (Palette::classic().marked().rgb(), [0; 3]);
+        assert_eq!(vec![marked, marked, dead, dead, marked, marked, dead, dead], game.to_image(&Viewport::new(3, 4, 1), 1).pixels[..8]);
+
+        let game = Game::new(Field::from_cells(".O..\nOO..\n...."), AutomataRule::cgol());
+        let mut viewport = Viewport::new(1, 1, 1);
+        viewport.pan((0, 1), 3, 4);
+        let image = game.to_image(&viewport, 2);
+        assert_eq!((2, 2), (image.width, image.height));
+        assert_eq!(vec![Palette::classic().age(0).rgb(); 4], image.pixels);
+        assert_eq!(8 * 6, game.to_image(&Viewport::new(3, 4, 1), 2).pixels.len());
+    }
+
+    #[test]
+    fn test_output_generations() {
+        let field = Field::from_cells("\
+....
+.OO.
+....");
+        let mut game = Game::new(field, AutomataRule::from("/2/3"));
+        block_on(game.next_iteration());
+
+        let output = game.to_string();
+        assert!(output.contains(&(Palette::classic().decay(2, 3).escape() + "\u{2588}\u{2588}")));
+        assert!(output.contains(&(Palette::classic().age(0).escape() + "\u{2588}\u{2588}")));
+
+        let game = game.with_palette(Palette::mono());
+        assert!(!game.to_string().contains("\x1B[38;") && !game.to_string().contains("\x1B[39m"));
+        assert!(!game.to_frame(&Viewport::new(3, 4, 1)).to_string().contains("\x1B[38;"));
+    }
+
+    #[test]
+    fn test_statistics() {
+        let mut game = Game::new(Field::from_cells(".....\n..O..\n..O..\n..O..\n....."), AutomataRule::cgol());
+        assert_eq!((3, (0, 0), None), (game.population(), game.births_deaths(), game.matches()));
+
+        block_on(game.next_iteration());
+        game.mark_pattern(&Field::pattern_from_cells("OOO"));
+        assert_eq!((3, (2, 2), Some(1)), (game.population(), game.births_deaths(), game.matches()));
+    }
+
+    #[test]
+    fn test_period() {
+        let mut blinker = Game::new(Field::from_cells(".....\n..O..\n..O..\n..O..\n....."), AutomataRule::cgol());
+        block_on(blinker.next_iteration());
+        assert_eq!(None, blinker.period());
+        block_on(blinker.next_iteration());
+        assert_eq!(Some(2), blinker.period());
+        blinker.set_cell(0, 0, true);
+        assert_eq!(None, blinker.period());
+
+        // A glider crosses a 5x5 torus diagonally in 20 generations.
+        let mut glider = Game::new(Field::from_cells(".O...\n..O..\nOOO..\n.....\n....."), AutomataRule::cgol());
+        for _ in 0..19 {
+            block_on(glider.next_iteration());
+            assert_eq!(None, glider.period());
+        }
+        block_on(glider.next_iteration());
+        assert_eq!(Some(20), glider.period());
+
+        // HashLife only sees every eighth generation, which is a multiple of the actual periods.
+        let mut block = Game::new(Field::from_cells("OO\nOO"), AutomataRule::cgol()).with_engine(Engine::HashLife(3));
+        block_on(block.next_iteration());
+        assert_eq!(Some(1), block.period());
+        let mut blinker = Game::new(Field::from_cells(".....\n..O..\n..O..\n..O..\n....."), AutomataRule::cgol()).with_engine(Engine::HashLife(3));
+        block_on(blinker.next_iteration());
+        assert_eq!(Some(2), blinker.period());
+        block_on(blinker.next_iteration());
+        assert_eq!(Some(2), blinker.period());
+    }
+
+    #[test]
+    fn test_ships() {
+        let ships = [("patterns/glider.cells", "(1,1)c/4"), ("patterns/copperhead.cells", "(0,-1)c/10"), ("patterns/56p6h1v0.cells", "(0,-1)c/6")];
+        for &(path, speed) in ships.iter() {
+            let mut field = Field::with_size(60, 60);
+            field.insert(Field::from_file(path).unwrap(), 20, 20);
+            let mut game = Game::new(field, AutomataRule::cgol()).with_ship_detection(true);
+
+            let mut seen = Vec::new();
+            for _ in 0..30 {
+                block_on(game.next_iteration());
+                seen.extend(game.ships().iter().map(|ship| ship.to_string()));
+            }
+            seen.dedup();
+            assert_eq!(vec![speed], seen, "{}", path);
+        }
+
+        let mut blinker = Game::new(Field::from_cells(".....\n..O..\n..O..\n..O..\n....."), AutomataRule::cgol()).with_ship_detection(true);
+        for _ in 0..4 {
+            block_on(blinker.next_iteration());
+            assert!(blinker.ships().is_empty());
+        }
+
+        let mut glider = Game::new(Field::from_file("patterns/glider.cells").unwrap().resized(20, 20), AutomataRule::cgol());
+        for _ in 0..10 {
+            block_on(glider.next_iteration());
+            assert!(glider.ships().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_set_cell() {
+        let glider = Field::from_cells(".O.\n..O\nOOO");
+        for &engine in [Engine::Packed, Engine::HashLife(0), Engine::Sparse].iter() {
+            let mut game = Game::new(Field::with_size(8, 8), AutomataRule::cgol()).with_engine(engine);
+            for (i, _) in glider.cells.iter().enumerate().filter(|(_, &alive)| alive) {
+                game.set_cell(1 + i / 3, 2 + i % 3, true);
+            }
+            game.set_cell(6, 6, true);
+            game.set_cell(6, 6, false);
+
+            let mut expected = Field::with_size(8, 8);
+            expected.insert(glider.clone(), 1, 2);
+            assert!(expected == *game.field());
+
+            block_on(game.next_iteration());
+            expected = Field::new(expected.next_generation(&AutomataRule::cgol(), 1), 8, 8);
+            assert!(expected == *game.field(), "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn test_history_stays_packed() {
+        let start = Field::from_random(20, 70);
+        let mut game = Game::new(start.clone(), AutomataRule::cgol()).with_history(10);
+        for _ in 0..5 {
+            block_on(game.next_iteration());
+        }
+        assert!(game.cells.field.get().is_none() && game.cells.states.get().is_none());
+
+        while game.rewind() {}
+        assert!(start == *game.field());
+    }
+
+    #[test]
+    fn test_rewind() {
+        // A glider stays inside the field, so the unbounded engines lose nothing when restarted from it.
+        let mut start = Field::with_size(12, 12);
+        start.insert(Field::from_cells(".O.\n..O\nOOO"), 4, 4);
+        for &engine in [Engine::Packed, Engine::Sparse, Engine::HashLife(0)].iter() {
+            let mut game = Game::new(start.clone(), AutomataRule::cgol()).with_engine(engine).with_history(3);
+            assert!(!game.rewind());
+
+            let mut generations = vec![start.clone()];
+            for _ in 0..5 {
+                block_on(game.next_iteration());
+                generations.push(game.field().clone());
+            }
+
+            for i in [4, 3, 2].iter() {
+                assert!(game.rewind());
+                assert!(generations[*i] == *game.field());
+                assert_eq!(*i, game.iterations);
+            }
+            assert!(!game.rewind());
+
+            block_on(game.next_iteration());
+            assert!(generations[3] == *game.field(), "{:?}", engine);
+        }
+    }
+
+    #[test]
+    fn test_move_to() {
+        // A glider leaves the 6x6 field through its bottom right corner and is found again by moving along.
+        for &engine in [Engine::Sparse, Engine::HashLife(2)].iter() {
+            let mut game = Game::new(Field::from_cells(".O....\n..O...\nOOO...\n......\n......\n......"), AutomataRule::cgol())
+                .with_engine(engine);
+            for _ in 0..40 / engine.generations() {
+                block_on(game.next_iteration());
+            }
+            assert_eq!(0, game.population(), "{:?}", engine);
+
+            game.move_to(10, 10);
+            assert!(Field::from_cells(".O....\n..O...\nOOO...\n......\n......\n......") == *game.field(), "{:?}", engine);
+            game.set_cell(5, 5, true);
+            game.move_to(12, 12);
+            assert!(game.field().cells[3 * 6 + 3]);
+        }
+    }
+
+    #[test]
+    fn test_halted() {
+        let mut game = Game::new(Field::from_cells(".O.\n..O\nOOO"), AutomataRule::cgol()).with_engine(Engine::HashLife(MAX_EXPONENT));
+        let mut frames = 0;
+        while !game.is_halted() {
+            block_on(game.next_iteration());
+            frames += 1;
+        }
+
+        // The last iteration is the one that couldn't be computed.
+        let iterations = game.iterations();
+        assert_eq!((frames - 1) << MAX_EXPONENT, iterations);
+        block_on(game.next_iteration());
+        assert_eq!(iterations, game.iterations());
+    }
+
+    #[test]
+    fn test_mark_pattern() {
+        // Gliders in four orientations and two phases, and a block that is none.
+        let scene = Field::from_cells("\
+............
+..O.....O...
+...O...O....
+.OOO...OOO..
+............
+............
+........O...
+.O.O...OO...
+..OO...O.O..
+..O.........
+..........OO
+..........OO");
+        let mut game = Game::new(scene, AutomataRule::cgol());
+        game.mark_pattern(&Field::pattern_from_file("patterns/glider.cells").unwrap());
+        assert_eq!(Some(4), game.matches());
+        assert_eq!(20, game.marked.cells.iter().filter(|&&marked| marked).count());
+
+        game.mark_pattern(&Field::pattern_from_cells("....\n.OO.\n.OO.\n...."));
+        assert_eq!(Some(1), game.matches());
+    }
+
+    #[test]
+    fn test_mark_isolated() {
+        let scene = Field::from_cells("\
+........
+.OO.....
+.OO.....
+........
+....OOO.
+....OO..
+........");
+        let block = Field::pattern_from_cells("OO\nOO");
+        let mut game = Game::new(scene.clone(), AutomataRule::cgol());
+        game.mark_pattern(&block);
+        assert_eq!(Some(2), game.matches());
+
+        let mut game = Game::new(scene, AutomataRule::cgol()).with_isolated_marks(true);
+        game.mark_pattern(&block);
+        assert_eq!(Some(1), game.matches());
+        assert!(game.marked.cells[9] && !game.marked.cells[36]);
+    }
+
     #[test]
     fn test_find_pattern() {
         {
-            let glider = Field::from_cells("\
+            let glider = Field::pattern_from_cells("\
 .O.
 ..O
 OOO");
@@ -174,7 +926,7 @@ OOOOO....
 
             let mut game = Game::new(scene, AutomataRule::cgol());
             game.mark_pattern(&glider);
-            println!("{}", game.to_string());
+            println!("{}", game);
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use crate::field::Field;
+use crate::rule::AutomataRule;
+
+type NodeId = u32;
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+/// Number of interned nodes after which the arena is rebuilt from the live root.
+const GC_THRESHOLD: usize = 1 << 22;
+/// Largest level of the root, whose size and coordinates `-2^(level-1)..2^(level-1)` have to fit in an `i64`.
+const MAX_LEVEL: u8 = 62;
+/// Largest exponent a step can have. The root grows to at least 2^(exponent + 3) cells across,
+/// and the levels left up to `MAX_LEVEL` are room for patterns to grow or move into.
+pub const MAX_EXPONENT: u8 = MAX_LEVEL - 8;
+
+/// A square of 2^level x 2^level cells. Level 0 nodes are single cells.
+#[derive(Clone, Copy)]
+struct Node {
+    level: u8,
+    children: [NodeId; 4],
+    population: u64,
+}
+
+/// Infinite plane stored as a hash-consed quadtree, advanced with HashLife.
+///
+/// The root of level `L` covers the cells `-2^(L-1)..2^(L-1)` in both directions,
+/// with row `r` and column `c` of a `Field` mapped to `(x, y) = (c, r)`.
+#[derive(Clone)]
+pub struct Universe {
+    nodes: Vec<Node>,
+    index: HashMap<[NodeId; 4], NodeId>,
+    results: HashMap<(NodeId, u8), NodeId>,
+    empties: Vec<NodeId>,
+    root: NodeId,
+    rule: AutomataRule,
+    pub generation: u64,
+}
+
+impl Universe {
+    pub fn new(rule: AutomataRule) -> Universe {
+        if rule.apply(false, 0) {
+            panic!("Rules with B0 are not supported by the hashlife engine");
+        }
+        if rule.states() > 2 {
+            panic!("Generations rules are not supported by the hashlife engine");
+        }
+        if rule.neighbourhood().radius() > 1 {
+            panic!("Neighbourhoods beyond the adjacent cells are not supported by the hashlife engine");
+        }
+
+        let leaf = |population| Node { level: 0, children: [DEAD; 4], population };
+        let mut universe = Universe {
+            nodes: vec![leaf(0), leaf(1)],
+            index: HashMap::new(),
+            results: HashMap::new(),
+            empties: vec![DEAD],
+            root: DEAD,
+            rule,
+            generation: 0,
+        };
+        universe.root = universe.empty(3);
+        universe
+    }
+
+    /// Starts with the live cells of `field`, its top left cell at (`left`, `top`).
+    pub fn from_field(field: &Field<bool>, top: i64, left: i64, rule: AutomataRule) -> Universe {
+        let mut universe = Universe::new(rule);
+        for (i, _) in field.cells.iter().enumerate().filter(|(_, &alive)| alive) {
+            universe.set(left + (i % field.columns) as i64, top + (i / field.columns) as i64, true);
+        }
+        universe
+    }
+
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, alive: bool) {
+        loop {
+            let half = 1i64 << (self.level(self.root) - 1);
+            if x >= -half && x < half && y >= -half && y < half {
+                break;
+            }
+            self.expand();
+        }
+
+        let half = 1i64 << (self.level(self.root) - 1);
+        self.root = self.set_node(self.root, x + half, y + half, alive);
+    }
+
+    /// Advances the universe by 2^`exponent` generations. Returns `false` and leaves the cells as they
+    /// are if the root would have to grow past `MAX_LEVEL` or the generation count would overflow.
+    pub fn step(&mut self, exponent: u8) -> bool {
+        assert!(exponent <= MAX_EXPONENT, "Can't step more than 2^{} generations at once", MAX_EXPONENT);
+        if self.nodes.len() > GC_THRESHOLD {
+            self.collect_garbage();
+        }
+
+        let generation = match self.generation.checked_add(1 << exponent) {
+            Some(generation) => generation,
+            None => return false,
+        };
+        while self.level(self.root) < exponent + 3 || !self.is_padded() {
+            if self.level(self.root) == MAX_LEVEL {
+                return false;
+            }
+            self.expand();
+        }
+
+        self.root = self.next(self.root, exponent);
+        self.generation = generation;
+        true
+    }
+
+    /// Copies the `rows` x `columns` window whose top left cell is (`left`, `top`) into a field.
+    pub fn to_field(&self, top: i64, left: i64, rows: usize, columns: usize) -> Field<bool> {
+        let mut field = Field::with_size(rows, columns);
+        let half = 1i64 << (self.level(self.root) - 1);
+        self.fill(self.root, -half - left, -half - top, &mut field);
+        field
+    }
+
+    fn fill(&self, node: NodeId, x: i64, y: i64, field: &mut Field<bool>) {
+        let n = self.nodes[node as usize];
+        let size = 1i64 << n.level;
+        if n.population == 0 || x >= field.columns as i64 || y >= field.rows as i64 || x + size <= 0 || y + size <= 0 {
+            return;
+        }
+
+        if n.level == 0 {
+            field.cells[y as usize * field.columns + x as usize] = true;
+            return;
+        }
+
+        let half = size / 2;
+        self.fill(n.children[0], x, y, field);
+        self.fill(n.children[1], x + half, y, field);
+        self.fill(n.children[2], x, y + half, field);
+        self.fill(n.children[3], x + half, y + half, field);
+    }
+
+    fn level(&self, node: NodeId) -> u8 {
+        self.nodes[node as usize].level
+    }
+
+    fn children(&self, node: NodeId) -> [NodeId; 4] {
+        self.nodes[node as usize].children
+    }
+
+    fn join(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        let children = [nw, ne, sw, se];
+        if let Some(&id) = self.index.get(&children) {
+            return id;
+        }
+
+        let population = children.iter()
+            .map(|&c| self.nodes[c as usize].population)
+            .fold(0u64, u64::saturating_add);
+        let level = self.level(nw) + 1;
+
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node { level, children, population });
+        self.index.insert(children, id);
+        id
+    }
+
+    fn empty(&mut self, level: u8) -> NodeId {
+        while self.empties.len() <= level as usize {
+            let e = *self.empties.last().unwrap();
+            let next = self.join(e, e, e, e);
+            self.empties.push(next);
+        }
+        self.empties[level as usize]
+    }
+
+    fn set_node(&mut self, node: NodeId, x: i64, y: i64, alive: bool) -> NodeId {
+        let level = self.level(node);
+        if level == 0 {
+            return if alive { ALIVE } else { DEAD };
+        }
+
+        let half = 1i64 << (level - 1);
+        let quadrant = (y >= half) as usize * 2 + (x >= half) as usize;
+        let mut children = self.children(node);
+        children[quadrant] = self.set_node(children[quadrant], x % half, y % half, alive);
+        self.join(children[0], children[1], children[2], children[3])
+    }
+
+    /// Surrounds the root with empty space, doubling its size while keeping it centred.
+    fn expand(&mut self) {
+        let [nw, ne, sw, se] = self.children(self.root);
+        let e = self.empty(self.level(self.root) - 1);
+
+        let nw = self.join(e, e, e, nw);
+        let ne = self.join(e, e, ne, e);
+        let sw = self.join(e, sw, e, e);
+        let se = self.join(se, e, e, e);
+        self.root = self.join(nw, ne, sw, se);
+    }
+
+    /// Whether all live cells lie in the central quarter of the central quarter of the root,
+    /// so that nothing escapes the result of `next`.
+    fn is_padded(&mut self) -> bool {
+        let center = self.center(self.root);
+        let inner = self.center(center);
+        self.nodes[inner as usize].population == self.population()
+    }
+
+    fn center(&mut self, node: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.children(node);
+        let nw = self.children(nw)[3];
+        let ne = self.children(ne)[2];
+        let sw = self.children(sw)[1];
+        let se = self.children(se)[0];
+        self.join(nw, ne, sw, se)
+    }
+
+    /// The centre of `node` (one level down) advanced by 2^`exponent` generations,
+    /// where `exponent` is at most `level - 2`.
+    fn next(&mut self, node: NodeId, exponent: u8) -> NodeId {
+        if let Some(&result) = self.results.get(&(node, exponent)) {
+            return result;
+        }
+
+        let n = self.nodes[node as usize];
+        let result = if n.population == 0 {
+            self.empty(n.level - 1)
+        } else if n.level == 2 {
+            self.next_base(node)
+        } else {
+            self.next_recursive(node, exponent)
+        };
+
+        self.results.insert((node, exponent), result);
+        result
+    }
+
+    /// Applies the rule once to the inner 2x2 cells of a 4x4 node.
+    fn next_base(&mut self, node: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        for (q, &child) in self.children(node).iter().enumerate() {
+            for (qq, &leaf) in self.children(child).iter().enumerate() {
+                cells[(q / 2) * 2 + qq / 2][(q % 2) * 2 + qq % 2] = leaf == ALIVE;
+            }
+        }
+
+        let mut next = [DEAD; 4];
+        for (i, cell) in next.iter_mut().enumerate() {
+            let (r, c) = (1 + i / 2, 1 + i % 2);
+            let alive = |(dr, dc): (i32, i32)| cells[(r as i32 + dr) as usize][(c as i32 + dc) as usize];
+            let next = match self.rule.is_totalistic() {
+                true => {
+                    let count = self.rule.neighbourhood().offsets().iter().filter(|&&delta| alive(delta)).count();
+                    self.rule.apply(cells[r][c], count)
+                }
+                false => {
+                    // Neighbours in the order N, NE, E, SE, S, SW, W, NW.
+                    let configuration = [(-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1)]
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |acc, (bit, &delta)| acc | (alive(delta) as u8) << bit);
+                    self.rule.apply_configuration(cells[r][c], configuration)
+                }
+            };
+            if next {
+                *cell = ALIVE;
+            }
+        }
+
+        self.join(next[0], next[1], next[2], next[3])
+    }
+
+    fn next_recursive(&mut self, node: NodeId, exponent: u8) -> NodeId {
+        let level = self.level(node);
+        let [nw, ne, sw, se] = self.children(node);
+        let [_, nw_ne, nw_sw, nw_se] = self.children(nw);
+        let [ne_nw, _, ne_sw, ne_se] = self.children(ne);
+        let [sw_nw, sw_ne, _, sw_se] = self.children(sw);
+        let [se_nw, se_ne, se_sw, _] = self.children(se);
+
+        let n01 = self.join(nw_ne, ne_nw, nw_se, ne_sw);
+        let n10 = self.join(nw_sw, nw_se, sw_nw, sw_ne);
+        let n11 = self.join(nw_se, ne_sw, sw_ne, se_nw);
+        let n12 = self.join(ne_sw, ne_se, se_nw, se_ne);
+        let n21 = self.join(sw_ne, se_nw, sw_se, se_sw);
+        let ring = [nw, n01, ne, n10, n11, n12, sw, n21, se];
+
+        // At full speed both halves advance 2^(level-3) generations, otherwise only the second does.
+        let full_speed = exponent == level - 2;
+        let mut a = [DEAD; 9];
+        for (i, &n) in ring.iter().enumerate() {
+            a[i] = if full_speed { self.next(n, level - 3) } else { self.center(n) };
+        }
+
+        let second = if full_speed { level - 3 } else { exponent };
+        let quadrants = [
+            self.join(a[0], a[1], a[3], a[4]),
+            self.join(a[1], a[2], a[4], a[5]),
+            self.join(a[3], a[4], a[6], a[7]),
+            self.join(a[4], a[5], a[7], a[8]),
+        ];
+        let mut c = [DEAD; 4];
+        for (i, &q) in quadrants.iter().enumerate() {
+            c[i] = self.next(q, second);
+        }
+
+        self.join(c[0], c[1], c[2], c[3])
+    }
+
+    /// Rebuilds the arena with only the nodes reachable from the root, dropping memoized results.
+    fn collect_garbage(&mut self) {
+        let mut fresh = Universe::new(self.rule.clone());
+        let mut copied = HashMap::new();
+        fresh.root = fresh.copy_from(self, self.root, &mut copied);
+        fresh.generation = self.generation;
+        *self = fresh;
+    }
+
+    fn copy_from(&mut self, other: &Universe, node: NodeId, copied: &mut HashMap<NodeId, NodeId>) -> NodeId {
+        if node == DEAD || node == ALIVE {
+            return node;
+        }
+        if let Some(&id) = copied.get(&node) {
+            return id;
+        }
+
+        let [nw, ne, sw, se] = other.children(node);
+        let children = [
+            self.copy_from(other, nw, copied),
+            self.copy_from(other, ne, copied),
+            self.copy_from(other, sw, copied),
+            self.copy_from(other, se, copied),
+        ];
+        let id = self.join(children[0], children[1], children[2], children[3]);
+        copied.insert(node, id);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::Field;
+    use crate::hashlife::{Universe, MAX_EXPONENT};
+    use crate::rule::AutomataRule;
+
+    fn naive(field: &Field<bool>, rule: &AutomataRule, generations: usize) -> Field<bool> {
+        let mut field = field.clone();
+        for _ in 0..generations {
+            field = Field::new(field.next_generation(rule, 1), field.rows, field.columns);
+        }
+        field
+    }
+
+    #[test]
+    fn test_set_get() {
+        let mut universe = Universe::new(AutomataRule::cgol());
+        universe.set(-1000, 7, true);
+        universe.set(3, -2, true);
+        assert!(universe.to_field(7, -1000, 1, 1).cells[0]);
+        assert_eq!(vec![true, false], universe.to_field(-2, 3, 2, 1).cells);
+        assert_eq!(2, universe.population());
+    }
+
+    #[test]
+    fn test_matches_naive() {
+        for rule in ["B3/S23", "B36/S23", "B3/S12345", "B2-a/S12", "B3/S23-a4i", "B2/S34H", "B1/S1V"].iter().map(|&r| AutomataRule::from(r)) {
+            let mut soup = Field::with_size(96, 96);
+            soup.insert(Field::from_random(16, 16), 40, 40);
+
+            let mut universe = Universe::from_field(&soup, 0, 0, rule.clone());
+            let mut expected = soup;
+            for &exponent in [0u8, 2, 1, 3].iter() {
+                universe.step(exponent);
+                expected = naive(&expected, &rule, 1 << exponent);
+                assert!(expected == universe.to_field(0, 0, 96, 96));
+            }
+            assert_eq!(15, universe.generation);
+        }
+    }
+
+    #[test]
+    fn test_glider_far_away() {
+        let glider = Field::from_cells("\
+.O.
+..O
+OOO");
+        let mut universe = Universe::from_field(&glider, 0, 0, AutomataRule::cgol());
+        universe.step(20);
+
+        // A glider moves one cell diagonally every four generations.
+        let shift = (1 << 20) / 4;
+        assert_eq!(5, universe.population());
+        assert!(glider == universe.to_field(shift, shift, 3, 3));
+    }
+
+    #[test]
+    fn test_glider_at_max_exponent() {
+        let glider = Field::from_cells("\
+.O.
+..O
+OOO");
+        let mut universe = Universe::from_field(&glider, 0, 0, AutomataRule::cgol());
+        for _ in 0..20 {
+            assert!(universe.step(MAX_EXPONENT));
+        }
+        let shift = 20 * (1 << MAX_EXPONENT) / 4;
+        assert!(glider == universe.to_field(shift, shift, 3, 3));
+
+        // Sooner or later the glider reaches the edge of what the coordinates can hold.
+        let mut frames = 20;
+        while universe.step(MAX_EXPONENT) {
+            frames += 1;
+        }
+        assert!(frames < 1000);
+        assert_eq!(5, universe.population());
+        assert_eq!(frames << MAX_EXPONENT, universe.generation);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+
+use crate::bitfield::BitField;
+
+/// A generation and its iteration count. The states are run-length encoded, each run `(length, state)`,
+/// or kept packed as the packed engine has them. Apart from them the generations the live cells were
+/// born in are run-length encoded, as dead and dying cells have no age. Cells of different ages thus
+/// don't break up the runs of states.
+struct Snapshot {
+    states: States,
+    born: Vec<(u32, usize)>,
+    iterations: usize,
+}
+
+enum States {
+    Runs(Vec<(u32, u8)>),
+    Packed(BitField),
+}
+
+/// The most recent generations, run-length encoded and at most `limit` of them.
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    limit: usize,
+}
+
+impl History {
+    pub fn new(limit: usize) -> History {
+        History { snapshots: VecDeque::new(), limit }
+    }
+
+    /// Records a generation, dropping the oldest one beyond the limit.
+    pub fn push(&mut self, states: &[u8], born: &[usize], iterations: usize) {
+        let alive = states.iter().zip(born).filter(|&(&state, _)| state == 1).map(|(_, &born)| born);
+        let born = runs(alive);
+        self.record(Snapshot { states: States::Runs(runs(states.iter().copied())), born, iterations });
+    }
+
+    /// Records a generation of the packed engine without unpacking it.
+    pub fn push_packed(&mut self, bits: &BitField, born: &[usize], iterations: usize) {
+        let born = runs(bits.alive().into_iter().map(|idx| born[idx]));
+        self.record(Snapshot { states: States::Packed(bits.clone()), born, iterations });
+    }
+
+    fn record(&mut self, snapshot: Snapshot) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.limit {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.limit > 0
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Removes the latest generation and returns its states, birth generations and iteration count.
+    /// Cells that aren't alive get 0 as their birth generation.
+    pub fn pop(&mut self) -> Option<(Vec<u8>, Vec<usize>, usize)> {
+        let snapshot = self.snapshots.pop_back()?;
+        let states = match snapshot.states {
+            States::Runs(runs) => expand(&runs),
+            States::Packed(bits) => bits.to_cells().into_iter().map(u8::from).collect(),
+        };
+        let mut alive = expand(&snapshot.born).into_iter();
+        let born = states.iter().map(|&state| match state {
+            1 => alive.next().unwrap_or(0),
+            _ => 0,
+        }).collect();
+        Some((states, born, snapshot.iterations))
+    }
+}
+
+fn runs<T: Copy + PartialEq>(values: impl Iterator<Item = T>) -> Vec<(u32, T)> {
+    let mut runs: Vec<(u32, T)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((length, v)) if *v == value => *length += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs
+}
+
+fn expand<T: Copy>(runs: &[(u32, T)]) -> Vec<T> {
+    runs.iter().flat_map(|&(length, value)| std::iter::repeat_n(value, length as usize)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bitfield::BitField;
+    use crate::field::Field;
+    use crate::history::{History, States};
+
+    #[test]
+    fn test_push_pop() {
+        let mut history = History::new(2);
+        history.push(&[0, 0, 1, 1, 2], &[0, 0, 3, 3, 0], 7);
+        history.push(&[1, 0, 0, 0, 0], &[0, 0, 0, 0, 0], 8);
+        history.push(&[0, 0, 0, 0, 1], &[5, 5, 5, 5, 4], 9);
+
+        assert!(matches!(&history.snapshots[0].states, States::Runs(runs) if runs.len() == 2));
+        assert_eq!(1, history.snapshots[0].born.len());
+        assert_eq!(Some((vec![0, 0, 0, 0, 1], vec![0, 0, 0, 0, 4], 9)), history.pop());
+        assert_eq!(Some((vec![1, 0, 0, 0, 0], vec![0; 5], 8)), history.pop());
+        assert_eq!(None, history.pop());
+    }
+
+    #[test]
+    fn test_live_runs() {
+        // Cells born in different generations break the runs of their ages, not those of their states.
+        let mut history = History::new(1);
+        history.push(&[1; 6], &[1, 1, 1, 4, 4, 4], 5);
+        assert!(matches!(&history.snapshots[0].states, States::Runs(runs) if runs.len() == 1));
+        assert_eq!(2, history.snapshots[0].born.len());
+        assert_eq!(Some((vec![1; 6], vec![1, 1, 1, 4, 4, 4], 5)), history.pop());
+    }
+
+    #[test]
+    fn test_packed() {
+        let mut history = History::new(1);
+        let bits = BitField::from(&Field::from_cells("O.O\n.O."));
+        history.push_packed(&bits, &[2, 9, 3, 9, 3, 9], 4);
+        assert_eq!(Some((vec![1, 0, 1, 0, 1, 0], vec![2, 0, 3, 0, 3, 0], 4)), history.pop());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut history = History::new(0);
+        history.push(&[1], &[0], 1);
+        assert_eq!(None, history.pop());
+    }
+}
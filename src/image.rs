@@ -0,0 +1,212 @@
+use itertools::Itertools;
+
+/// How frames are drawn on the terminal.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Output {
+    /// Block characters coloured with escape sequences.
+    Text,
+    /// Sixel images, supported by xterm, mlterm, foot and others.
+    Sixel,
+    /// Images in the Kitty graphics protocol.
+    Kitty,
+}
+
+impl From<&str> for Output {
+    fn from(txt: &str) -> Self {
+        match txt {
+            "text" => Output::Text,
+            "sixel" => Output::Sixel,
+            "kitty" => Output::Kitty,
+            _ => panic!("Unknown output \"{}\"", txt),
+        }
+    }
+}
+
+/// A picture of `width` x `height` pixels, each as red, green and blue.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    /// Encodes the image as a Sixel sequence with one colour register per colour in use.
+    /// Runs of more than three equal sixels are compressed.
+    pub fn to_sixel(&self) -> String {
+        let colors = self.pixels.iter().copied().unique().collect::<Vec<[u8; 3]>>();
+        let percent = |v: u8| (v as u32 * 100 + 127) / 255;
+
+        let mut output = format!("\x1BP0;0;0q\"1;1;{};{}", self.width, self.height);
+        for (register, &[r, g, b]) in colors.iter().enumerate() {
+            output += &format!("#{};2;{};{};{}", register, percent(r), percent(g), percent(b));
+        }
+
+        for top in (0..self.height).step_by(6) {
+            let band = top..(top + 6).min(self.height);
+            for (register, &color) in colors.iter().enumerate() {
+                let sixels = (0..self.width).map(|x| band.clone()
+                    .filter(|&y| self.pixels[y * self.width + x] == color)
+                    .fold(0u8, |bits, y| bits | 1 << (y - top)))
+                    .collect::<Vec<u8>>();
+                if sixels.iter().all(|&bits| bits == 0) {
+                    continue;
+                }
+
+                output += &format!("#{}", register);
+                for (count, bits) in sixels.into_iter().dedup_with_count() {
+                    let sixel = (63 + bits) as char;
+                    match count {
+                        1..=3 => output.extend(std::iter::repeat_n(sixel, count)),
+                        _ => output += &format!("!{}{}", count, sixel),
+                    }
+                }
+                output += "$";
+            }
+            output += "-";
+        }
+        output + "\x1B\\"
+    }
+
+    /// Encodes the image as 24 bit RGB for the Kitty graphics protocol, in chunks of at most
+    /// 4096 base64 bytes. Every frame replaces image 1 and asks the terminal not to respond.
+    pub fn to_kitty(&self) -> String {
+        let rgb = self.pixels.concat();
+        let data = base64(&rgb);
+        let chunks = data.as_bytes().chunks(4096).collect::<Vec<_>>();
+
+        let mut output = String::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let more = (i + 1 < chunks.len()) as u8;
+            let control = match i {
+                0 => format!("a=T,f=24,s={},v={},i=1,q=2,m={}", self.width, self.height, more),
+                _ => format!("m={}", more),
+            };
+            output += &format!("\x1B_G{};{}\x1B\\", control, std::str::from_utf8(chunk).unwrap());
+        }
+        output
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            output.push(match i <= chunk.len() {
+                true => ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char,
+                false => '=',
+            });
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::iter::Peekable;
+    use std::str::Chars;
+
+    use crate::image::{base64, Image, Output};
+    use crate::term::xterm_rgb;
+
+    /// Pixels of a Sixel sequence as RGB percentages, `None` where nothing was drawn.
+    fn decode_sixel(sixel: &str) -> (usize, usize, Vec<Option<[usize; 3]>>) {
+        let body = sixel.strip_prefix("\x1BP0;0;0q\"1;1;").unwrap().strip_suffix("\x1B\\").unwrap();
+        let end = body.find('#').unwrap();
+        let size = body[..end].split(';').map(|n| n.parse::<usize>().unwrap()).collect::<Vec<_>>();
+        let (width, height) = (size[0], size[1]);
+
+        let mut pixels = vec![None; width * height];
+        let mut registers = HashMap::new();
+        let (mut register, mut x, mut top) = (0, 0, 0);
+        let mut chars = body[end..].chars().peekable();
+        let number = |chars: &mut Peekable<Chars>| {
+            let mut n = 0;
+            while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                n = n * 10 + d;
+                chars.next();
+            }
+            n as usize
+        };
+
+        while let Some(c) = chars.next() {
+            match c {
+                '#' => {
+                    register = number(&mut chars);
+                    if chars.peek() == Some(&';') {
+                        let mut color = [0; 4];
+                        for value in color.iter_mut() {
+                            chars.next();
+                            *value = number(&mut chars);
+                        }
+                        assert_eq!(2, color[0], "only RGB colours are used");
+                        registers.insert(register, [color[1], color[2], color[3]]);
+                    }
+                }
+                '$' => x = 0,
+                '-' => {
+                    x = 0;
+                    top += 6;
+                }
+                '!' | '?'..='~' => {
+                    let (count, sixel) = match c {
+                        '!' => (number(&mut chars), chars.next().unwrap()),
+                        _ => (1, c),
+                    };
+                    for _ in 0..count {
+                        for dy in (0..6).filter(|dy| (sixel as usize - 63) & 1 << dy != 0) {
+                            pixels[(top + dy) * width + x] = Some(registers[&register]);
+                        }
+                        x += 1;
+                    }
+                }
+                _ => panic!("Unexpected {:?} in Sixel data", c),
+            }
+        }
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn test_output_from() {
+        assert_eq!(Output::Sixel, Output::from("sixel"));
+        assert_eq!(Output::Kitty, Output::from("kitty"));
+    }
+
+    #[test]
+    fn test_sixel() {
+        let (width, height) = (9, 8);
+        let pixels = (0..width * height).map(|i| match (i / width, i % width) {
+            (7, _) => 196,
+            (r, c) if r == c => 34,
+            (_, c) if c > 4 => 21,
+            _ => 0,
+        }).map(xterm_rgb).collect::<Vec<[u8; 3]>>();
+        let image = Image { width, height, pixels };
+
+        let sixel = image.to_sixel();
+        assert!(sixel.contains("!4"), "{:?}", sixel);
+
+        let percent = |color: [u8; 3]| color.map(|v| (v as usize * 100 + 127) / 255);
+        let (w, h, decoded) = decode_sixel(&sixel);
+        assert_eq!((width, height), (w, h));
+        assert_eq!(image.pixels.iter().map(|&p| Some(percent(p))).collect::<Vec<_>>(), decoded);
+    }
+
+    #[test]
+    fn test_kitty() {
+        assert_eq!("TWFu", base64(b"Man"));
+        assert_eq!("TWE=", base64(b"Ma"));
+        assert_eq!("TQ==", base64(b"M"));
+
+        let image = Image { width: 2, height: 1, pixels: vec![[0, 0, 255], [255, 0, 0]] };
+        assert_eq!("\x1B_Ga=T,f=24,s=2,v=1,i=1,q=2,m=0;AAD//wAA\x1B\\", image.to_kitty());
+
+        let large = Image { width: 100, height: 100, pixels: vec![[0; 3]; 100 * 100] }.to_kitty();
+        assert_eq!(10, large.matches("\x1B_G").count());
+        assert!(large.starts_with("\x1B_Ga=T,f=24,s=100,v=100,i=1,q=2,m=1;"));
+        assert!(large.contains("\x1B_Gm=1;AAAA"));
+        assert!(large.contains("\x1B_Gm=0;AAAA"));
+    }
+}
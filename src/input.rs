@@ -0,0 +1,130 @@
+use std::io::{stdin, Read};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+const ESC: u8 = 0x1B;
+
+/// Keyboard and mouse input.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Key {
+    /// Space pauses and resumes.
+    Pause,
+    /// `n` advances a single generation.
+    Step,
+    /// `b` goes back one generation.
+    Back,
+    /// `+` shortens the interval between generations.
+    Faster,
+    /// `-` lengthens the interval between generations.
+    Slower,
+    /// `q` or Ctrl+C.
+    Quit,
+    /// `e` enters and leaves the editor.
+    Edit,
+    /// `z` switches to the next zoom level.
+    Zoom,
+    /// `s` saves the field.
+    Save,
+    /// Enter toggles the cell under the cursor.
+    Toggle,
+    /// Arrow keys, as `(rows, columns)` to move the editor's cursor or the viewport by.
+    Move(i32, i32),
+    /// Left mouse button pressed (or moved while pressed if `drag`) at the 1-based terminal position.
+    Mouse { x: usize, y: usize, drag: bool },
+}
+
+impl Key {
+    pub fn from_byte(byte: u8) -> Option<Key> {
+        match byte {
+            b' ' => Some(Key::Pause),
+            b'n' => Some(Key::Step),
+            b'b' => Some(Key::Back),
+            b'+' => Some(Key::Faster),
+            b'-' => Some(Key::Slower),
+            b'q' | 0x03 => Some(Key::Quit),
+            b'e' => Some(Key::Edit),
+            b's' => Some(Key::Save),
+            b'z' => Some(Key::Zoom),
+            b'\r' | b'\n' => Some(Key::Toggle),
+            _ => None,
+        }
+    }
+}
+
+/// Parses the first key in `bytes` into the key, if any, and the number of bytes it took.
+/// Returns `None` while an escape sequence is incomplete.
+pub fn parse(bytes: &[u8]) -> Option<(Option<Key>, usize)> {
+    match bytes {
+        [] | [ESC] | [ESC, b'['] => None,
+        [ESC, b'[', b'<', rest @ ..] => {
+            let end = rest.iter().position(|&b| b == b'M' || b == b'm')?;
+            let fields = std::str::from_utf8(&rest[..end]).ok()
+                .map(|s| s.split(';').map(|n| n.parse::<usize>().ok()).collect::<Vec<_>>());
+
+            // SGR mouse reporting: button;column;row, pressed with M and released with m.
+            let key = match (fields.as_deref(), rest[end]) {
+                (Some(&[Some(button), Some(x), Some(y)]), b'M') if button == 0 || button == 32 => Some(Key::Mouse { x, y, drag: button == 32 }),
+                _ => None,
+            };
+            Some((key, end + 4))
+        }
+        [ESC, b'[', rest @ ..] => {
+            let end = rest.iter().position(|b| (0x40..=0x7E).contains(b))?;
+            let key = match (end, rest[end]) {
+                (0, b'A') => Some(Key::Move(-1, 0)),
+                (0, b'B') => Some(Key::Move(1, 0)),
+                (0, b'C') => Some(Key::Move(0, 1)),
+                (0, b'D') => Some(Key::Move(0, -1)),
+                _ => None,
+            };
+            Some((key, end + 3))
+        }
+        [byte, ..] => Some((Key::from_byte(*byte), 1)),
+    }
+}
+
+/// Reads keys from stdin on a background thread. The channel disconnects once stdin is closed.
+pub fn spawn_reader() -> Receiver<Key> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let mut pending = Vec::new();
+        let mut buffer = [0; 64];
+        while let Ok(n @ 1..) = stdin().lock().read(&mut buffer) {
+            pending.extend_from_slice(&buffer[..n]);
+            while let Some((key, length)) = parse(&pending) {
+                pending.drain(..length);
+                if let Some(key) = key {
+                    if sender.send(key).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{Key, parse};
+
+    #[test]
+    fn test_from_byte() {
+        let keys = b" n+-q\x03x".iter().map(|&b| Key::from_byte(b)).collect::<Vec<_>>();
+        assert_eq!(vec![Some(Key::Pause), Some(Key::Step), Some(Key::Faster), Some(Key::Slower), Some(Key::Quit), Some(Key::Quit), None], keys);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Some((Some(Key::Edit), 1)), parse(b"ex"));
+        assert_eq!(Some((Some(Key::Move(-1, 0)), 3)), parse(b"\x1B[Aq"));
+        assert_eq!(Some((Some(Key::Move(0, -1)), 3)), parse(b"\x1B[D"));
+        assert_eq!(Some((Some(Key::Mouse { x: 12, y: 5, drag: false }), 10)), parse(b"\x1B[<0;12;5M"));
+        assert_eq!(Some((Some(Key::Mouse { x: 3, y: 40, drag: true }), 11)), parse(b"\x1B[<32;3;40M"));
+        assert_eq!(Some((None, 10)), parse(b"\x1B[<0;12;5m"));
+        assert_eq!(Some((None, 4)), parse(b"\x1B[5~"));
+
+        assert_eq!(None, parse(b"\x1B"));
+        assert_eq!(None, parse(b"\x1B[<0;12"));
+    }
+}
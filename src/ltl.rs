@@ -0,0 +1,218 @@
+use std::thread;
+
+use regex::Regex;
+
+use crate::field::{band_rows, Field};
+use crate::rule::{generations_state, Neighbourhood};
+
+/// Larger than Life rule in the `R,C,M,S,B,N` notation, e.g. `R5,C0,M1,S34..58,B34..45,NM` for Bosco's rule.
+#[derive(Clone, Debug)]
+pub struct LtlRule {
+    range: usize,
+    /// Number of cell states; more than two adds dying states like in Generations rules.
+    states: u8,
+    /// Whether a cell counts itself as a neighbour.
+    middle: bool,
+    survival: (usize, usize),
+    birth: (usize, usize),
+    /// `Moore` for the square of the given range, `VonNeumann` for the diamond.
+    neighbourhood: Neighbourhood,
+}
+
+impl LtlRule {
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    pub fn range(&self) -> usize {
+        self.range
+    }
+
+    pub fn apply(&self, alive: bool, neighbours: usize) -> bool {
+        let (min, max) = if alive { self.survival } else { self.birth };
+        min <= neighbours && neighbours <= max
+    }
+
+    pub fn next_state(&self, state: u8, alive: bool) -> u8 {
+        generations_state(state, alive, self.states)
+    }
+
+    /// Live neighbours of every cell. Squares are read off a summed-area table in constant time,
+    /// diamonds as one rectangle per row.
+    pub fn calculate_neighbours(&self, field: &Field<bool>) -> Vec<usize> {
+        let table = SummedArea::new(field, self.range);
+        (0..field.cells.len()).map(|i| self.count(&table, field, i)).collect()
+    }
+
+    /// Counts neighbours and applies the rule in bands of rows, one band per thread.
+    pub fn next_generation(&self, field: &Field<bool>, threads: usize) -> Vec<bool> {
+        if threads <= 1 {
+            let neighbours = self.calculate_neighbours(field);
+            return field.cells.iter().zip(neighbours).map(|(&alive, n)| self.apply(alive, n)).collect();
+        }
+
+        let table = SummedArea::new(field, self.range);
+        let band = band_rows(field.rows, threads) * field.columns;
+        let mut new_cells = vec![false; field.cells.len()];
+
+        thread::scope(|s| {
+            for (b, chunk) in new_cells.chunks_mut(band).enumerate() {
+                let table = &table;
+                s.spawn(move || {
+                    for (i, cell) in chunk.iter_mut().enumerate() {
+                        let idx = b * band + i;
+                        *cell = self.apply(field.cells[idx], self.count(table, field, idx));
+                    }
+                });
+            }
+        });
+
+        new_cells
+    }
+
+    fn count(&self, table: &SummedArea, field: &Field<bool>, idx: usize) -> usize {
+        let (r, c) = (idx / field.columns, idx % field.columns);
+        let range = self.range;
+
+        let count = match self.neighbourhood {
+            Neighbourhood::VonNeumann => (0..=2 * range).map(|dr| {
+                let width = range - (dr as i64 - range as i64).unsigned_abs() as usize;
+                table.sum(r + dr, c + range - width, r + dr, c + range + width)
+            }).sum(),
+            _ => table.sum(r, c, r + 2 * range, c + 2 * range),
+        };
+
+        match self.middle || !field.cells[idx] {
+            true => count,
+            false => count - 1,
+        }
+    }
+}
+
+impl From<&str> for LtlRule {
+    fn from(txt: &str) -> Self {
+        let ltl = Regex::new(r"^R([0-9]+),C([0-9]+),M([01]),S([0-9]+)\.\.([0-9]+),B([0-9]+)\.\.([0-9]+),N([MN])$").unwrap();
+        let c = ltl.captures(txt).unwrap_or_else(|| panic!("Unknown rule format \"{}\"", txt));
+        let number = |group| c.get(group).unwrap().as_str().parse::<usize>()
+            .unwrap_or_else(|_| panic!("Number out of range in \"{}\"", txt));
+
+        let range = match number(1) {
+            0 => panic!("Larger than Life rules need a range of at least 1 in \"{}\"", txt),
+            range => range,
+        };
+        let states = match number(2) {
+            0..=2 => 2,
+            states if states <= u8::MAX as usize => states as u8,
+            _ => panic!("Unsupported number of states in \"{}\"", txt),
+        };
+        let neighbourhood = match c.get(8).unwrap().as_str() {
+            "N" => Neighbourhood::VonNeumann,
+            _ => Neighbourhood::Moore,
+        };
+
+        LtlRule {
+            range,
+            states,
+            middle: number(3) == 1,
+            survival: (number(4), number(5)),
+            birth: (number(6), number(7)),
+            neighbourhood,
+        }
+    }
+}
+
+/// Summed-area table of a field padded by `range` cells on every side, resolved through its topology.
+struct SummedArea {
+    sums: Vec<usize>,
+    width: usize,
+}
+
+impl SummedArea {
+    fn new(field: &Field<bool>, range: usize) -> SummedArea {
+        let (height, width) = (field.rows + 2 * range, field.columns + 2 * range);
+        let mut sums = vec![0; (height + 1) * (width + 1)];
+
+        for r in 0..height {
+            let mut row = 0;
+            for c in 0..width {
+                let cell = field.topology.resolve(r as i64 - range as i64, c as i64 - range as i64, field.rows, field.columns);
+                row += cell.map_or(0, |(rr, cc)| field.cells[rr * field.columns + cc] as usize);
+                sums[(r + 1) * (width + 1) + c + 1] = sums[r * (width + 1) + c + 1] + row;
+            }
+        }
+
+        SummedArea { sums, width: width + 1 }
+    }
+
+    /// Live cells in the padded rows `top..=bottom` and columns `left..=right`.
+    fn sum(&self, top: usize, left: usize, bottom: usize, right: usize) -> usize {
+        let at = |r: usize, c: usize| self.sums[r * self.width + c];
+        at(bottom + 1, right + 1) + at(top, left) - at(top, right + 1) - at(bottom + 1, left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::Field;
+    use crate::ltl::LtlRule;
+    use crate::rule::{AutomataRule, Neighbourhood};
+    use crate::topology::Topology;
+
+    #[test]
+    fn test_from() {
+        let bosco = LtlRule::from("R5,C0,M1,S34..58,B34..45,NM");
+        assert_eq!(5, bosco.range);
+        assert_eq!(2, bosco.states());
+        assert!(bosco.middle);
+        assert_eq!((34, 58), bosco.survival);
+        assert_eq!((34, 45), bosco.birth);
+        assert_eq!(Neighbourhood::Moore, bosco.neighbourhood);
+
+        let diamond = LtlRule::from("R10,C5,M0,S2..3,B3..3,NN");
+        assert_eq!(5, diamond.states());
+        assert_eq!(Neighbourhood::VonNeumann, diamond.neighbourhood);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_unknown() {
+        let _ = LtlRule::from("R5,C0,M1,S34..58,B34..45");
+    }
+
+    #[test]
+    fn test_range_one_matches_life() {
+        let pairs = [("R1,C0,M0,S2..3,B3..3,NM", "B3/S23"), ("R1,C0,M0,S1..1,B1..2,NN", "B12/S1V")];
+        for &(ltl, life) in pairs.iter() {
+            let (ltl, life) = (LtlRule::from(ltl), AutomataRule::from(life));
+            for &topology in [Topology::Torus, Topology::Plane, Topology::Klein].iter() {
+                let field = Field::from_random(23, 31).with_topology(topology);
+                assert_eq!(field.next_generation(&life, 1), ltl.next_generation(&field, 1));
+                assert_eq!(field.next_generation(&life, 1), ltl.next_generation(&field, 3));
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighbours_match_brute_force() {
+        for &rule in ["R4,C0,M1,S0..0,B0..0,NM", "R4,C0,M0,S0..0,B0..0,NN", "R9,C0,M1,S0..0,B0..0,NM"].iter() {
+            let rule = LtlRule::from(rule);
+            let range = rule.range as i32;
+            for &topology in [Topology::Torus, Topology::CylinderV, Topology::CrossSurface].iter() {
+                let field = Field::from_random(12, 17).with_topology(topology);
+                let counts = rule.calculate_neighbours(&field);
+
+                for (i, &count) in counts.iter().enumerate() {
+                    let (r, c) = (i / field.columns, i % field.columns);
+                    let expected = (-range..=range)
+                        .flat_map(|dr| (-range..=range).map(move |dc| (dr, dc)))
+                        .filter(|&(dr, dc)| rule.neighbourhood == Neighbourhood::Moore || dr.abs() + dc.abs() <= range)
+                        .filter(|&delta| rule.middle || delta != (0, 0))
+                        .filter_map(|delta| field.topology.offset((r, c), delta, field.rows, field.columns))
+                        .filter(|&(rr, cc)| field.cells[rr * field.columns + cc])
+                        .count();
+                    assert_eq!(expected, count, "{:?} at {}x{}", topology, r, c);
+                }
+            }
+        }
+    }
+}
@@ -2,21 +2,50 @@ extern crate pest;
 #[macro_use]
 extern crate pest_derive;
 
-use std::{thread, time};
+use std::{env, thread, time};
+use std::path::PathBuf;
+use std::sync::mpsc::RecvTimeoutError;
 use std::io::{stdout, Stdout, Write};
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, Error, ErrorKind};
 use futures::executor::block_on;
 
-use crate::field::Field;
-use crate::game::Game;
-use crate::rule::AutomataRule;
+use crate::census::Census;
+use crate::editor::Editor;
+use crate::field::{writable_extension, Field, Pattern};
+use crate::game::{Engine, Game};
+use crate::hashlife::MAX_EXPONENT;
+use crate::image::Output;
+use crate::input::Key;
+use crate::palette::Palette;
+use crate::rule::{AutomataRule, Neighbourhood, Rule};
+use crate::screen::{Frame, Screen};
+use crate::status::{Meter, Status};
 use crate::term::*;
+use crate::topology::Topology;
+use crate::viewport::Viewport;
 
 mod game;
 mod field;
+mod bitfield;
+mod census;
+mod cycle;
+mod editor;
+mod hashlife;
+mod history;
+mod image;
+mod input;
+mod ltl;
+mod object;
+mod palette;
+mod plane;
+mod topology;
 mod term;
 mod rule;
+mod screen;
+mod spaceship;
+mod status;
+mod viewport;
 
 // 24 - 1 (Iterations) - 2 (Horizontal Line)
 const TERM_DEFAULT_ROWS: usize = 24 - 1 - 2;
@@ -24,36 +53,111 @@ const TERM_DEFAULT_COLUMNS: usize = 80;
 
 fn main() {
     let matches = App::new("Conway's Game of Life").author("w177us")
-        .about("Run with e.g. `cgol -c $COLUMNS -r $[ LINES-3 ] -t 30'")
-        .arg(Arg::with_name("rows").short('r').about("Number of rows").takes_value(true))
-        .arg(Arg::with_name("columns").short('c').about("Number of columns").takes_value(true))
+        .about("Run with e.g. `cgol -c 400 -r 300 -t 30' and pan with the arrow keys")
+        .arg(Arg::with_name("rows").short('r').about("Number of rows (default: fill the terminal)").takes_value(true))
+        .arg(Arg::with_name("columns").short('c').about("Number of columns (default: fill the terminal)").takes_value(true))
         .arg(Arg::with_name("interval").short('t').about("Tick interval (in ms)").takes_value(true))
         .arg(Arg::with_name("highres").short('x').about("Use high resolution"))
-        .arg(Arg::with_name("mark").short('m').takes_value(true).about("Mark pattern"))
+        .arg(Arg::with_name("braille").long("braille").about("Use Braille patterns of 2x4 cells per character at the high resolution zoom level"))
+        .arg(Arg::with_name("output").long("output").possible_values(&["text", "sixel", "kitty"]).about("Draw with characters or as Sixel or Kitty graphics protocol images"))
+        .arg(Arg::with_name("pixels").long("pixels").takes_value(true).about("Pixels per cell for image output (default: 1)"))
+        .arg(Arg::with_name("palette").long("palette").takes_value(true).about("classic, gradient, fire, mono or a palette from the config file (default: classic, mono if NO_COLOR is set)"))
+        .arg(Arg::with_name("palette-file").long("palette-file").takes_value(true).about("Config file with user-defined palettes (default: ~/.config/cgol/palettes.conf)"))
+        .arg(Arg::with_name("max-age").long("max-age").takes_value(true).about("Age at which true colour palettes reach their last colour"))
+        .arg(Arg::with_name("stop-on-stable").long("stop-on-stable").about("Quit once the whole field repeats itself, i.e. has settled into still lifes and oscillators"))
+        .arg(Arg::with_name("resize-field").long("resize-field").about("Resize the field along with the terminal, keeping its cells centred"))
+        .arg(Arg::with_name("mark").short('m').takes_value(true).about("Mark pattern, `?` cells in it match anything"))
+        .arg(Arg::with_name("ships").long("ships").about("Look for spaceships and show their velocity in the status line"))
+        .arg(Arg::with_name("isolated").long("isolated").about("Only mark patterns with nothing but dead cells right around them"))
         .arg(Arg::with_name("insert").short('i').takes_value(true).about("Insert pattern"))
+        .arg(Arg::with_name("dump").long("dump").takes_value(true).validator(writable).about("Write the last generation to a .rle or .cells file on exit"))
+        .arg(Arg::with_name("save").long("save").takes_value(true).validator(writable).about("File the `s` key saves the field to (default: cgol.rle)"))
+        .arg(Arg::with_name("history").long("history").takes_value(true).about("Number of generations kept to go back to with `b` (default: 100)"))
         .arg(Arg::with_name("init").long("init").possible_values(&["empty", "random", "gauss"]))
-        .arg(Arg::with_name("rule").long("rule").takes_value(true).about("Cellular automaton rule, e.g. B36/S23 for highlife."))
+        .arg(Arg::with_name("rule").long("rule").takes_value(true).about("Cellular automaton rule, e.g. B36/S23 for highlife, B2-a/S12 in Hensel notation, /2/3 for Brian's Brain, B2/S34H on a hexagonal grid or R5,C0,M1,S34..58,B34..45,NM for Larger than Life."))
+        .arg(Arg::with_name("neighbourhood").long("neighbourhood").takes_value(true).about("moore, von-neumann, hexagonal or a mask like .O./O.O/.O. centred on the cell"))
+        .arg(Arg::with_name("topology").long("topology")
+            .possible_values(&["torus", "plane", "cylinder-h", "cylinder-v", "klein", "cross-surface"])
+            .about("How the edges of the field are joined"))
+        .arg(Arg::with_name("threads").long("threads").takes_value(true).about("Number of worker threads (default: all cores)"))
+        .arg(Arg::with_name("engine").long("engine").possible_values(&["naive", "packed", "hashlife", "sparse"]).about("Simulation backend, hashlife and sparse run on an unbounded plane"))
+        .arg(Arg::with_name("step").long("step").takes_value(true).about("Generations per frame for the hashlife engine, e.g. 2^10"))
+        .subcommand(App::new("census").about("Run random soups until they settle and count the objects left by apgcode")
+            .arg(Arg::with_name("soups").long("soups").takes_value(true).about("Number of soups (default: 1)"))
+            .arg(Arg::with_name("size").long("size").takes_value(true).about("Rows and columns of each soup (default: 16)"))
+            .arg(Arg::with_name("seed").long("seed").takes_value(true).about("Seed of the first soup, the others count up from it (default: random)"))
+            .arg(Arg::with_name("rule").long("rule").takes_value(true).about("Rule with two states, e.g. B36/S23 (default: B3/S23)")))
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("census") {
+        census(matches);
+        return;
+    }
+
     let highres = matches.is_present("highres");
+    let braille = matches.is_present("braille");
 
-    let rows = matches.value_of("rows").map(|s| s.to_string())
-        .or_else(|| call("tput", "lines"))
-        .and_then(|s| s.parse::<usize>().ok())
-        .map(|i| (i - 3) * if highres { 2 } else { 1 })
-        .unwrap_or(TERM_DEFAULT_ROWS);
+    let zoom = if highres || braille { 2 } else { 1 };
+    let viewport = Viewport::new(0, 0, zoom);
+    let viewport = if braille { viewport.with_braille() } else { viewport };
+    let (scale_rows, scale_columns) = viewport.scale();
 
-    let columns = matches.value_of("columns").map(|s| s.to_string())
-        .or_else(|| call("tput", "cols"))
-        .and_then(|s| s.parse::<usize>().ok())
-        .map(|i| i * (if highres { 2 } else { 1 }))
-        .unwrap_or(TERM_DEFAULT_COLUMNS);
+    let resize_field = matches.is_present("resize-field");
+    let stop_on_stable = matches.is_present("stop-on-stable");
+    let (screen_rows, screen_columns) = screen_size();
+
+    // The field fills the screen unless given a size, in which case the viewport pans over it.
+    let rows = matches.value_of("rows").map(|v| v.parse::<usize>().unwrap()).unwrap_or(screen_rows * scale_rows);
+    let columns = matches.value_of("columns").map(|v| v.parse::<usize>().unwrap()).unwrap_or(screen_columns * scale_columns);
 
-    let interval = matches.value_of("interval").map(|v| v.parse::<u64>().unwrap()).unwrap_or(30);
-    let mark = matches.value_of("mark").map(|p| Field::from_file(p).expect("Couldn't open file"));
+    let output = matches.value_of("output").map(Output::from).unwrap_or(Output::Text);
+    let pixels = matches.value_of("pixels").map(|v| v.parse::<usize>().unwrap()).unwrap_or(1);
+    let palette_file = matches.value_of("palette-file").map(PathBuf::from).or_else(Palette::config_path);
+    let palette = match matches.value_of("palette") {
+        Some(name) => Palette::named(name)
+            .or_else(|| palette_file.and_then(|path| Palette::from_file(&path, name)))
+            .unwrap_or_else(|| panic!("Unknown palette \"{}\"", name)),
+        None if env::var_os("NO_COLOR").is_some() => Palette::mono(),
+        None => Palette::classic(),
+    };
+    let palette = match matches.value_of("max-age") {
+        Some(max_age) => palette.with_max_age(max_age.parse::<u32>().unwrap()),
+        None => palette,
+    };
+    let mut interval = matches.value_of("interval").map(|v| v.parse::<u64>().unwrap()).unwrap_or(30);
+    let mark = matches.value_of("mark").map(|p| Field::pattern_from_file(p).expect("Couldn't open file"));
+    let isolated = matches.is_present("isolated");
+    let ships = matches.is_present("ships");
     let insert = matches.value_of("insert").map(|p| Field::from_file(p).expect("Couldn't open file"));
     let init = matches.value_of("init");
-    let rule = matches.value_of("rule").map(AutomataRule::from).unwrap_or_else(AutomataRule::cgol);
+    let dump = matches.value_of("dump");
+    let save = matches.value_of("save").unwrap_or("cgol.rle");
+    let rule_name = matches.value_of("rule").unwrap_or("B3/S23");
+    let rule = Rule::from(rule_name);
+    let rule = match matches.value_of("neighbourhood") {
+        Some(n) => rule.with_neighbourhood(Neighbourhood::from(n)),
+        None => rule,
+    };
+    let topology = matches.value_of("topology").map(Topology::from).unwrap_or(Topology::Torus);
+    let step = matches.value_of("step").map(parse_step).unwrap_or(0);
+    let engine = match matches.value_of("engine") {
+        Some("naive") => Engine::Naive,
+        Some("hashlife") => Engine::HashLife(step),
+        Some("sparse") => Engine::Sparse,
+        _ => Engine::Packed
+    };
+    // The unbounded engines only take two-state neighbourhood rules.
+    let unsupported = match &rule {
+        Rule::Ltl(_) => Some("Larger than Life"),
+        Rule::Automata(rule) if rule.states() > 2 => Some("Generations"),
+        Rule::Automata(_) => None,
+    };
+    if let (Some(kind), Engine::HashLife(_) | Engine::Sparse) = (unsupported, engine) {
+        fail(format!("{} rules are not supported by --engine {}", kind, matches.value_of("engine").unwrap()), ErrorKind::ArgumentConflict);
+    }
+    let history = matches.value_of("history").map(|v| v.parse::<usize>().unwrap()).unwrap_or(100);
+    let threads = matches.value_of("threads").map(|v| v.parse::<usize>().unwrap())
+        .unwrap_or_else(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
 
     let mut stdout = stdout();
 
@@ -62,40 +166,227 @@ fn main() {
         Some("random") => Field::from_random(rows, columns),
         Some("gauss") => Field::from_normal_distribution(rows, columns),
         _ => Field::from_random(rows, columns)
-    };
+    }.with_topology(topology);
 
     if let Some(pattern) = insert { field.insert(pattern, 0, 0) }
 
-    let mut game = Game::new(field, rule);
+    let mut game = Game::new(field, rule).with_engine(engine).with_threads(threads).with_history(history).with_palette(palette)
+        .with_isolated_marks(isolated).with_ship_detection(ships);
+
+    let keys = input::spawn_reader();
+    // Dropped after raw mode, also when unwinding from a panic.
+    let reset = ResetGuard;
+    let raw_mode = RawMode::enter();
+    watch_resize();
+    let mut paused = false;
+    let mut step = false;
+    let mut viewport = Viewport { rows: screen_rows, columns: screen_columns, ..viewport };
+    if matches!(engine, Engine::HashLife(_) | Engine::Sparse) { viewport = viewport.with_plane() }
+    let mut editor: Option<Editor> = None;
+    let mut redraw = false;
+    let mut screen = Screen::new();
+    let mut meter = Meter::new();
+    let mut message: Option<String> = None;
 
     block_on(print(&mut stdout, gfx_cls()));
+    block_on(print(&mut stdout, gfx_hide_cursor()));
 
     loop {
-        let perf_complete = time::Instant::now();
-        block_on(iteration(highres, &mark, &mut stdout, &mut game));
-        let perf_complete_ms = perf_complete.elapsed().as_millis();
+        if take_resized() {
+            let (screen_rows, screen_columns) = screen_size();
+            if resize_field {
+                let (scale_rows, scale_columns) = viewport.scale();
+                let old = (game.field().rows, game.field().columns);
+                game.resize(screen_rows * scale_rows, screen_columns * scale_columns);
+                if let Some(editor) = editor.as_mut() { editor.resize(old, (game.field().rows, game.field().columns)) }
+            }
+            viewport.resize(screen_rows, screen_columns, game.field().rows, game.field().columns);
+            block_on(print(&mut stdout, gfx_cls()));
+            screen.invalidate();
+            redraw = paused;
+        }
 
-        if interval - perf_complete_ms as u64 > 0 {
-            thread::sleep(time::Duration::from_millis(interval));
-        };
+        // While paused the frame shows the game's current generation; while running it lags one behind.
+        if !paused || step {
+            match paused {
+                true => block_on(advance(&mark, &mut game)),
+                false => {
+                    let status = Status::of(&game, rule_name, meter.rate(), interval, paused).with_message(message.as_deref()).to_string();
+                    block_on(iteration(&frame(&mut screen, &game, &viewport, output, pixels, status), &mark, &mut stdout, &mut game))
+                }
+            }
+            meter.tick(game.iterations());
+            redraw = step || (stop_on_stable && game.period().is_some()) || game.is_halted();
+            step = false;
+        }
+        if redraw {
+            let cursor = editor.as_ref().map(|e| e.gfx_cursor(&viewport)).unwrap_or_default();
+            let status = Status::of(&game, rule_name, meter.rate(), interval, paused).with_message(message.as_deref()).to_string();
+            block_on(print(&mut stdout, &(frame(&mut screen, &game, &viewport, output, pixels, status) + &cursor)));
+            redraw = false;
+        }
+        if (stop_on_stable && game.period().is_some()) || game.is_halted() {
+            break;
+        }
+
+        match keys.recv_timeout(time::Duration::from_millis(interval)) {
+            Ok(Key::Pause) if editor.is_none() => {
+                paused = !paused;
+                redraw = paused;
+            }
+            Ok(Key::Step) => step = paused,
+            Ok(Key::Back) => {
+                paused = true;
+                game.rewind();
+                redraw = true;
+            }
+            Ok(Key::Faster) => interval = interval.saturating_sub(10),
+            Ok(Key::Slower) => interval += 10,
+            Ok(Key::Quit) => break,
+            // A failed save is shown below the field until the next one, the game goes on either way.
+            Ok(Key::Save) => {
+                message = game.field().to_file(save, rule_name).err().map(|e| format!("Couldn't save {}: {}", save, e));
+                redraw = paused;
+            }
+            // Editing pauses the game, shows the cells 1:1 and reports the mouse until `e` is pressed again.
+            Ok(Key::Edit) => {
+                let (rows, columns) = (game.field().rows, game.field().columns);
+                editor = match editor.take() {
+                    Some(editor) => {
+                        viewport.set_zoom(editor.zoom, rows, columns);
+                        None
+                    }
+                    None => {
+                        let editor = Editor::new(&viewport);
+                        viewport.set_zoom(1, rows, columns);
+                        Some(editor)
+                    }
+                };
+                block_on(print(&mut stdout, gfx_cls()));
+                screen.invalidate();
+                paused = editor.is_some();
+                redraw = editor.is_some();
+                block_on(print(&mut stdout, gfx_mouse(editor.is_some())));
+                block_on(print(&mut stdout, gfx_hide_cursor()));
+            }
+            Ok(Key::Move(dr, dc)) if editor.is_none() => {
+                viewport.pan((dr, dc), game.field().rows, game.field().columns);
+                if let Some((top, left)) = viewport.origin { game.move_to(top, left) }
+                redraw = paused;
+            }
+            Ok(Key::Zoom) if editor.is_none() => {
+                viewport.cycle_zoom(game.field().rows, game.field().columns);
+                block_on(print(&mut stdout, gfx_cls()));
+                screen.invalidate();
+                redraw = paused;
+            }
+            Ok(key) => if let Some(editor) = editor.as_mut() { redraw = editor.apply(key, &mut game, &mut viewport) },
+            Err(RecvTimeoutError::Timeout) => {}
+            // Without a keyboard there is nothing to wait for but the interval.
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(time::Duration::from_millis(interval)),
+        }
+    }
+
+    drop(raw_mode);
+    drop(reset);
+    if let Some(path) = dump {
+        if let Err(e) = game.field().to_file(path, rule_name) {
+            fail(format!("Couldn't write {}: {}", path, e), ErrorKind::Io);
+        }
+    }
+    if let Some(period) = game.period() {
+        println!("Stable at generation {} with period {}", game.iterations(), period);
+    }
+    if game.is_halted() {
+        println!("Stopped at generation {}, the cells can't be followed any further", game.iterations());
     }
 }
 
-async fn iteration(highres: bool, mark: &Option<Field<bool>>, stdout: &mut Stdout, game: &mut Game) {
-    let gfx = match highres {
-        true => game.to_string_highres(),
-        false => game.to_string()
-    };
+async fn iteration(gfx: &str, mark: &Option<Pattern>, stdout: &mut Stdout, game: &mut Game) {
+    let future_print = print(stdout, gfx);
+    let future_cells = advance(mark, game);
 
-    let future_print = print(stdout, gfx.as_str());
-    let future_cells = async {
-        game.next_iteration().await;
-        if let Some(pattern) = mark.as_ref() { game.mark_pattern(pattern) }
+    futures::join!(future_print, future_cells);
+}
+
+/// The current generation drawn as text or an image with the status below it, padded or cut to the width of the terminal.
+fn frame(screen: &mut Screen, game: &Game, viewport: &Viewport, output: Output, pixels: usize, status: String) -> String {
+    let status = format!("{:1$}", status.chars().take(viewport.columns).collect::<String>(), viewport.columns);
+    let image = match output {
+        Output::Text => return screen.draw(Frame { status, ..game.to_frame(viewport) }),
+        Output::Sixel => game.to_image(viewport, pixels).to_sixel(),
+        Output::Kitty => game.to_image(viewport, pixels).to_kitty(),
     };
+    format!("{}{}\n{}", gfx_pos1(), image, status)
+}
 
-    futures::join!(future_print, future_cells);
+async fn advance(mark: &Option<Pattern>, game: &mut Game) {
+    game.next_iteration().await;
+    if let Some(pattern) = mark.as_ref() { game.mark_pattern(pattern) }
+}
+
+/// Runs the soups of the `census` subcommand and prints the table of objects they left.
+fn census(matches: &ArgMatches) {
+    let soups = matches.value_of("soups").map(|v| v.parse::<u64>().unwrap()).unwrap_or(1);
+    let size = matches.value_of("size").map(|v| v.parse::<usize>().unwrap()).unwrap_or(16);
+    let seed = matches.value_of("seed").map(|v| v.parse::<u64>().unwrap());
+    let rule = matches.value_of("rule").map(AutomataRule::from).unwrap_or_else(AutomataRule::cgol);
+
+    let mut census = Census::new();
+    for i in 0..soups {
+        let soup = match seed {
+            Some(seed) => Field::from_seed(size, size, seed + i),
+            None => Field::from_random(size, size),
+        };
+        // Room around the soup for its debris and gliders before they wrap around.
+        let mut field = Field::with_size(8 * size, 8 * size);
+        field.insert(soup, 7 * size / 2, 7 * size / 2);
+        census.add_soup(field, &rule);
+    }
+    print!("{}", census);
+}
+
+/// Rows and columns of the terminal available to the field, i.e. without the lines above and below it.
+fn screen_size() -> (usize, usize) {
+    let rows = call("tput", "lines")
+        .and_then(|s| s.parse::<usize>().ok())
+        .map(|i| i.saturating_sub(3))
+        .unwrap_or(TERM_DEFAULT_ROWS);
+
+    let columns = call("tput", "cols")
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(TERM_DEFAULT_COLUMNS);
+
+    (rows, columns)
+}
+
+/// Accepts the files `Field::to_file` can write to, ahead of running for as long as it takes to get there.
+fn writable(path: &str) -> Result<(), String> {
+    writable_extension(path).map(|_| ()).map_err(|e| format!("{}\n", e))
+}
+
+/// Exits with `message` the way clap reports invalid arguments.
+fn fail(message: String, kind: ErrorKind) -> ! {
+    Error::with_description(message + "\n", kind).expect("Couldn't write error").exit()
 }
 
+/// Parses `2^k` or a power of two into the exponent `k`.
+fn parse_step(step: &str) -> u8 {
+    let k = match step.strip_prefix("2^") {
+        Some(k) => k.parse::<u8>().expect("Invalid step exponent"),
+        None => {
+            let n = step.parse::<u64>().expect("Invalid step");
+            if !n.is_power_of_two() {
+                panic!("Step {} is not a power of two", n);
+            }
+            n.trailing_zeros() as u8
+        }
+    };
+    if k > MAX_EXPONENT {
+        panic!("Step 2^{} is too large, at most 2^{} generations per frame are supported", k, MAX_EXPONENT);
+    }
+    k
+}
 
 #[allow(unused_must_use)]
 async fn print(stdout: &mut Stdout, field: &str) {
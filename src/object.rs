@@ -0,0 +1,298 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::field::Field;
+use crate::rule::AutomataRule;
+use crate::topology::Topology;
+
+/// A group of non-dead cells with no other such cell within `2 * reach` of it, so that nothing else
+/// can affect it in the next generation. Objects crossing a wrapping edge are kept in one piece.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Object {
+    /// Top left corner of the bounding box in the field.
+    pub row: usize,
+    pub column: usize,
+    pub height: usize,
+    pub width: usize,
+    /// Positions relative to the top left corner and states, sorted.
+    pub cells: Vec<(usize, usize, u8)>,
+}
+
+impl Object {
+    /// The object made of `cells` at unwrapped positions, `place` putting its top left corner into the field.
+    fn from_cells(cells: Vec<(i64, i64, u8)>, place: impl Fn(i64, i64) -> (usize, usize)) -> Object {
+        let top = cells.iter().map(|&(r, _, _)| r).min().unwrap();
+        let left = cells.iter().map(|&(_, c, _)| c).min().unwrap();
+        let mut cells = cells.into_iter()
+            .map(|(r, c, state)| ((r - top) as usize, (c - left) as usize, state))
+            .collect::<Vec<_>>();
+        cells.sort_unstable();
+
+        let (row, column) = place(top, left);
+        Object {
+            row,
+            column,
+            height: cells.iter().map(|&(r, _, _)| r + 1).max().unwrap(),
+            width: cells.iter().map(|&(_, c, _)| c + 1).max().unwrap(),
+            cells,
+        }
+    }
+
+    /// The pieces of a `rows` x `columns` torus as one object, each piece put the short way round from the first.
+    fn merge(pieces: &[Object], rows: usize, columns: usize) -> Object {
+        let closest = |d: i64, size: usize| match d.rem_euclid(size as i64) {
+            d if d > size as i64 / 2 => d - size as i64,
+            d => d,
+        };
+        let (row, column) = (pieces[0].row as i64, pieces[0].column as i64);
+        let cells = pieces.iter().flat_map(|piece| {
+            let top = row + closest(piece.row as i64 - row, rows);
+            let left = column + closest(piece.column as i64 - column, columns);
+            piece.cells.iter().map(move |&(r, c, state)| (top + r as i64, left + c as i64, state))
+        }).collect();
+        Object::from_cells(cells, wrap(rows, columns))
+    }
+
+    /// Hash of the object's shape and states regardless of where it is.
+    pub fn shape_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.height, self.width, &self.cells).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The object's live cells on their own, `margin` dead cells around them on every side.
+    pub fn to_field(&self, margin: usize) -> Field<bool> {
+        let columns = self.width + 2 * margin;
+        let mut field = Field::with_size(self.height + 2 * margin, columns);
+        for &(r, c, state) in self.cells.iter() {
+            field.cells[(r + margin) * columns + c + margin] = state == 1;
+        }
+        field
+    }
+}
+
+/// Wraps positions into a `rows` x `columns` field.
+fn wrap(rows: usize, columns: usize) -> impl Fn(i64, i64) -> (usize, usize) {
+    move |r, c| (r.rem_euclid(rows as i64) as usize, c.rem_euclid(columns as i64) as usize)
+}
+
+/// Splits the non-dead cells of `states` into objects, cells at most `2 * reach` apart belonging to the same one.
+pub fn objects(states: &Field<u8>, reach: usize) -> Vec<Object> {
+    let distance = 2 * reach.max(1) as i32;
+    let mut visited = vec![false; states.cells.len()];
+    let mut objects = Vec::new();
+
+    for start in 0..states.cells.len() {
+        if states.cells[start] == 0 || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+
+        // Positions are unwrapped, i.e. relative to the start even across edges.
+        let mut cells = Vec::new();
+        let mut queue = vec![(start, (start / states.columns) as i64, (start % states.columns) as i64)];
+        while let Some((idx, r, c)) = queue.pop() {
+            cells.push((r, c, states.cells[idx]));
+            let (row, column) = (idx / states.columns, idx % states.columns);
+            // Away from the edges neighbours need no help from the topology.
+            let inner = row >= distance as usize && row + (distance as usize) < states.rows
+                && column >= distance as usize && column + (distance as usize) < states.columns;
+            for dr in -distance..=distance {
+                for dc in -distance..=distance {
+                    let next = match inner {
+                        true => Some(((row as i32 + dr) as usize, (column as i32 + dc) as usize)),
+                        false => states.topology.offset((row, column), (dr, dc), states.rows, states.columns),
+                    };
+                    if let Some((rr, cc)) = next {
+                        let i = rr * states.columns + cc;
+                        if states.cells[i] != 0 && !visited[i] {
+                            visited[i] = true;
+                            queue.push((i, r + dr as i64, c + dc as i64));
+                        }
+                    }
+                }
+            }
+        }
+
+        objects.push(Object::from_cells(cells, wrap(states.rows, states.columns)));
+    }
+    objects
+}
+
+/// Where the cell at (`row`, `column`) of a `rows` x `columns` box ends up in orientation `i` of 8.
+pub fn orient(i: usize, (row, column): (usize, usize), (rows, columns): (usize, usize)) -> (usize, usize) {
+    // Bit 0 mirrors the columns, bit 1 the rows and bit 2 swaps them.
+    let row = if i & 2 == 0 { row } else { rows - 1 - row };
+    let column = if i & 1 == 0 { column } else { columns - 1 - column };
+    if i & 4 == 0 { (row, column) } else { (column, row) }
+}
+
+/// The phases the object goes through on its own under `rule`, one per generation starting with itself,
+/// and whether it recurs elsewhere. `None` if it dies out or doesn't recur within `max_period` generations.
+/// Phases that fall apart into several pieces are kept whole.
+pub fn phases(object: &Object, rule: &AutomataRule, max_period: usize) -> Option<(Vec<Object>, bool)> {
+    let reach = rule.neighbourhood().radius().max(1);
+    let margin = 8 * reach;
+    let mut field = object.to_field(margin).with_topology(Topology::Torus);
+    let mut phases = vec![object.clone()];
+
+    for _ in 0..max_period {
+        field.cells = field.next_generation(rule, 1);
+        let current = match objects(&Field::from_alive(&field), reach).as_slice() {
+            [] => return None,
+            [current] => current.clone(),
+            pieces => Object::merge(pieces, field.rows, field.columns),
+        };
+        if current.cells == object.cells {
+            return Some((phases, (current.row, current.column) != (margin, margin)));
+        }
+        phases.push(current);
+    }
+    None
+}
+
+/// Splits the object into the parts that evolve on their own under `rule`, the way apgsearch takes apart
+/// pseudo objects such as a bi-block. Touching cells start out in the same part, and parts that change
+/// each other within the object's period are joined. Objects that don't recur within `max_period`
+/// generations stay whole. Parts are placed relative to the object, not wrapped into its field.
+pub fn split(object: &Object, rule: &AutomataRule, max_period: usize) -> Vec<Object> {
+    let period = match phases(object, rule, max_period) {
+        Some((phases, _)) => phases.len(),
+        None => return vec![object.clone()],
+    };
+    let reach = rule.neighbourhood().radius().max(1);
+    let margin = 8 * reach;
+    let close = |(r, c): (usize, usize), (rr, cc): (usize, usize)| r.abs_diff(rr).max(c.abs_diff(cc)) <= reach;
+
+    let cells = &object.cells;
+    let mut labels = (0..cells.len()).collect::<Vec<_>>();
+    let join = |labels: &mut Vec<usize>, from: usize, to: usize| labels.iter_mut().filter(|l| **l == from).for_each(|l| *l = to);
+    for i in 0..cells.len() {
+        for j in 0..i {
+            if close((cells[i].0, cells[i].1), (cells[j].0, cells[j].1)) {
+                let (from, to) = (labels[j], labels[i]);
+                join(&mut labels, from, to);
+            }
+        }
+    }
+
+    loop {
+        let mut parts = labels.clone();
+        parts.sort_unstable();
+        parts.dedup();
+
+        let mut whole = object.to_field(margin).with_topology(Topology::Torus);
+        let mut fields = parts.iter().map(|&part| {
+            let mut field = Field::with_size(whole.rows, whole.columns).with_topology(Topology::Torus);
+            for (&(r, c, state), _) in cells.iter().zip(&labels).filter(|(_, &l)| l == part) {
+                field.cells[(r + margin) * whole.columns + c + margin] = state == 1;
+            }
+            field
+        }).collect::<Vec<_>>();
+
+        // Parts around the first cell that comes out differently on their own than together.
+        let mut joined = None;
+        for _ in 0..period {
+            let next = whole.next_generation(rule, 1);
+            let nexts = fields.iter().map(|field| field.next_generation(rule, 1)).collect::<Vec<_>>();
+            if let Some(idx) = (0..next.len()).find(|&i| next[i] != nexts.iter().any(|cells| cells[i])) {
+                let at = (idx / whole.columns, idx % whole.columns);
+                joined = Some(parts.iter().zip(&fields).filter(|(_, field)| {
+                    (-(reach as i32)..=reach as i32).any(|dr| (-(reach as i32)..=reach as i32).any(|dc| {
+                        field.topology.offset(at, (dr, dc), field.rows, field.columns)
+                            .is_some_and(|(r, c)| field.cells[r * field.columns + c])
+                    }))
+                }).map(|(&part, _)| part).collect::<Vec<_>>());
+                break;
+            }
+            whole.cells = next;
+            for (field, cells) in fields.iter_mut().zip(nexts) {
+                field.cells = cells;
+            }
+        }
+
+        match joined {
+            None => break,
+            // Two parts at least changed the cell, unless the rule makes cells appear out of nowhere.
+            Some(near) => {
+                let near = if near.len() < 2 { parts } else { near };
+                for &part in near.iter() {
+                    join(&mut labels, part, near[0]);
+                }
+            }
+        }
+    }
+
+    let mut parts = labels.clone();
+    parts.sort_unstable();
+    parts.dedup();
+    parts.into_iter().map(|part| {
+        let cells = cells.iter().zip(&labels).filter(|(_, &l)| l == part)
+            .map(|(&(r, c, state), _)| ((object.row + r) as i64, (object.column + c) as i64, state))
+            .collect();
+        Object::from_cells(cells, |r, c| (r as usize, c as usize))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::Field;
+    use crate::object::{objects, orient, phases};
+    use crate::rule::AutomataRule;
+    use crate::topology::Topology;
+
+    #[test]
+    fn test_objects() {
+        let field = Field::from_cells("\
+O......O
+O.......
+........
+...OO...
+...O.O..
+........
+........
+O......O");
+        let objects = objects(&Field::from_alive(&field), 1);
+        assert_eq!(2, objects.len());
+
+        // The corners are one object across the edges of the torus.
+        assert_eq!((7, 7, 3, 2), (objects[0].row, objects[0].column, objects[0].height, objects[0].width));
+        assert_eq!(vec![(0, 0, 1), (0, 1, 1), (1, 0, 1), (1, 1, 1), (2, 1, 1)], objects[0].cells);
+        assert_eq!((3, 3, 2, 3), (objects[1].row, objects[1].column, objects[1].height, objects[1].width));
+
+        let plane = Field::from_alive(&field.with_topology(Topology::Plane));
+        assert_eq!(5, super::objects(&plane, 1).len());
+    }
+
+    #[test]
+    fn test_shape_hash() {
+        let field = Field::from_alive(&Field::from_cells("OO......\nOO......\n........\n........\n....OO..\n....OO..\n........\n........"));
+        let blocks = objects(&field, 1);
+        assert_eq!(2, blocks.len());
+        assert_eq!(blocks[0].shape_hash(), blocks[1].shape_hash());
+        assert!(blocks[0].to_field(1) == Field::from_cells("....\n.OO.\n.OO.\n...."));
+    }
+
+    #[test]
+    fn test_orient() {
+        assert_eq!((0, 1), orient(0, (0, 1), (2, 3)));
+        assert_eq!((0, 1), orient(1, (0, 1), (2, 3)));
+        assert_eq!((1, 2), orient(3, (0, 0), (2, 3)));
+        assert_eq!((1, 0), orient(4, (0, 1), (2, 3)));
+    }
+
+    #[test]
+    fn test_phases() {
+        let rule = AutomataRule::cgol();
+        let glider = &objects(&Field::from_alive(&Field::from_cells(".....\n..O..\n...O.\n.OOO.\n.....")), 1)[0];
+        let (glider_phases, moved) = phases(glider, &rule, 100).unwrap();
+        assert_eq!((4, true), (glider_phases.len(), moved));
+
+        let blinker = &objects(&Field::from_alive(&Field::from_cells(".....\n.OOO.\n.....")), 1)[0];
+        let (blinker_phases, moved) = phases(blinker, &rule, 100).unwrap();
+        assert_eq!((2, false), (blinker_phases.len(), moved));
+
+        let domino = &objects(&Field::from_alive(&Field::from_cells("....\n.OO.\n....")), 1)[0];
+        assert!(phases(domino, &rule, 100).is_none());
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::term::xterm_rgb;
+
+/// Age at which true colour palettes reach their last colour unless configured otherwise.
+const MAX_AGE: u32 = 50;
+
+/// A foreground colour.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Color {
+    /// The terminal's own colour.
+    Default,
+    /// One of the xterm 256 colours.
+    Indexed(u8),
+    /// A 24 bit true colour.
+    Rgb([u8; 3]),
+}
+
+impl Color {
+    pub fn escape(&self) -> String {
+        match self {
+            Color::Default => "\x1B[39m".to_string(),
+            Color::Indexed(i) => format!("\x1B[38;5;{}m", i),
+            Color::Rgb([r, g, b]) => format!("\x1B[38;2;{};{};{}m", r, g, b),
+        }
+    }
+
+    /// Red, green and blue for image output, white for the default colour.
+    pub fn rgb(&self) -> [u8; 3] {
+        match *self {
+            Color::Default => [255; 3],
+            Color::Indexed(i) => xterm_rgb(i),
+            Color::Rgb(rgb) => rgb,
+        }
+    }
+}
+
+/// Parses `#rrggbb`, an xterm colour index from 0 to 255 or `default`.
+impl From<&str> for Color {
+    fn from(txt: &str) -> Self {
+        let hex = |i: usize| txt.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match txt {
+            "default" => Color::Default,
+            _ if txt.len() == 7 && txt.starts_with('#') => match (hex(1), hex(3), hex(5)) {
+                (Some(r), Some(g), Some(b)) => Color::Rgb([r, g, b]),
+                _ => panic!("Invalid colour \"{}\"", txt),
+            },
+            _ => Color::Indexed(txt.parse::<u8>().unwrap_or_else(|_| panic!("Invalid colour \"{}\"", txt))),
+        }
+    }
+}
+
+/// Colours of live cells by age, of dying Generations states, of marked cells and of the lines around the field.
+///
+/// True colour palettes fade through `ages` until `max_age`; palettes with indexed colours take one step per generation.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Palette {
+    ages: Vec<Color>,
+    decay: Vec<Color>,
+    marked: Color,
+    line: Color,
+    max_age: u32,
+}
+
+impl Palette {
+    /// Green to blue in 256 colours, one shade per generation.
+    pub fn classic() -> Palette {
+        Palette {
+            ages: [34, 35, 36, 37, 38, 39, 21].iter().map(|&i| Color::Indexed(i)).collect(),
+            decay: [226, 220, 214, 208, 202, 196, 160, 124].iter().map(|&i| Color::Indexed(i)).collect(),
+            marked: Color::Indexed(1),
+            line: Color::Indexed(15),
+            max_age: MAX_AGE,
+        }
+    }
+
+    /// No colours at all, for terminals and pipes without colour support.
+    pub fn mono() -> Palette {
+        Palette { ages: vec![Color::Default], decay: vec![Color::Default], marked: Color::Default, line: Color::Default, max_age: MAX_AGE }
+    }
+
+    /// The built-in palettes `classic`, `mono`, `gradient` and `fire`.
+    pub fn named(name: &str) -> Option<Palette> {
+        let rgb = |colors: &[&str]| colors.iter().map(|&c| Color::from(c)).collect::<Vec<_>>();
+        match name {
+            "classic" => Some(Palette::classic()),
+            "mono" => Some(Palette::mono()),
+            "gradient" => Some(Palette {
+                ages: rgb(&["#00af00", "#00d7af", "#00afff", "#0000ff"]),
+                decay: rgb(&["#ffff00", "#ff8700", "#d70000", "#5f0000"]),
+                ..Palette::classic()
+            }),
+            "fire" => Some(Palette {
+                ages: rgb(&["#ffffd7", "#ffff00", "#ff8700", "#d70000", "#5f0000"]),
+                decay: rgb(&["#8a8a8a", "#4e4e4e", "#262626"]),
+                marked: Color::Rgb([0, 175, 255]),
+                ..Palette::classic()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Palettes defined in a config file with a `[name]` section each and `key = value` lines below.
+    /// Keys are `ages` and `decay` with space separated colours, `marked`, `line` and `max-age`;
+    /// missing keys are taken from the classic palette. Lines starting with `#` are comments.
+    pub fn parse_config(txt: &str) -> HashMap<String, Palette> {
+        let mut palettes = HashMap::new();
+        let mut current: Option<(String, Palette)> = None;
+
+        for line in txt.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                palettes.extend(current.take());
+                current = Some((name.trim().to_string(), Palette::classic()));
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').unwrap_or_else(|| panic!("Expected `key = value` in \"{}\"", line));
+            let (_, palette) = current.as_mut().unwrap_or_else(|| panic!("\"{}\" is outside of a [palette] section", line));
+            let colors = || value.split_whitespace().map(Color::from).collect::<Vec<_>>();
+            match key.trim() {
+                "ages" => palette.ages = colors(),
+                "decay" => palette.decay = colors(),
+                "marked" => palette.marked = Color::from(value.trim()),
+                "line" => palette.line = Color::from(value.trim()),
+                "max-age" => palette.max_age = value.trim().parse().unwrap_or_else(|_| panic!("Invalid max-age in \"{}\"", line)),
+                key => panic!("Unknown palette key \"{}\"", key),
+            }
+            if palette.ages.is_empty() || palette.decay.is_empty() {
+                panic!("Palettes need at least one colour in \"{}\"", line);
+            }
+        }
+        palettes.extend(current);
+        palettes
+    }
+
+    /// The palette `name` from the config file at `path`, if the file exists and defines it.
+    pub fn from_file(path: &Path, name: &str) -> Option<Palette> {
+        let txt = fs::read_to_string(path).ok()?;
+        Palette::parse_config(&txt).remove(name)
+    }
+
+    /// `$XDG_CONFIG_HOME/cgol/palettes.conf`, falling back to `~/.config`.
+    pub fn config_path() -> Option<PathBuf> {
+        let config = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config.join("cgol").join("palettes.conf"))
+    }
+
+    /// The escape sequence of `color`, none at all if the palette only has the terminal's own colour.
+    pub fn escape(&self, color: Color) -> String {
+        let mono = self.ages.iter().chain(&self.decay).chain([&self.marked, &self.line]).all(|&c| c == Color::Default);
+        match mono {
+            true => String::new(),
+            false => color.escape(),
+        }
+    }
+
+    pub fn with_max_age(mut self, max_age: u32) -> Palette {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn age(&self, age: u32) -> Color {
+        let last = self.ages.len() - 1;
+        if last == 0 || !self.ages.iter().all(|c| matches!(c, Color::Rgb(_))) {
+            return self.ages[(age as usize).min(last)];
+        }
+
+        let position = age.min(self.max_age) as f32 / self.max_age.max(1) as f32 * last as f32;
+        let i = (position as usize).min(last - 1);
+        let t = position - i as f32;
+        let (from, to) = (self.ages[i].rgb(), self.ages[i + 1].rgb());
+        Color::Rgb([0, 1, 2].map(|k| (from[k] as f32 + (to[k] as f32 - from[k] as f32) * t).round() as u8))
+    }
+
+    /// Colour of the dying `state` out of `states`, fading through `decay`.
+    pub fn decay(&self, state: u8, states: u8) -> Color {
+        let step = (state.saturating_sub(2) as usize * self.decay.len()) / states.saturating_sub(2).max(1) as usize;
+        self.decay[step.min(self.decay.len() - 1)]
+    }
+
+    pub fn marked(&self) -> Color {
+        self.marked
+    }
+
+    pub fn line(&self) -> Color {
+        self.line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::palette::{Color, Palette};
+
+    #[test]
+    fn test_color_from() {
+        assert_eq!(Color::Rgb([255, 135, 0]), Color::from("#ff8700"));
+        assert_eq!(Color::Indexed(21), Color::from("21"));
+        assert_eq!(Color::Default, Color::from("default"));
+        assert_eq!("\x1B[38;2;255;135;0m", Color::from("#ff8700").escape());
+        assert_eq!("\x1B[39m", Color::Default.escape());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("", Palette::mono().escape(Color::Default));
+        let marked = Palette { marked: Color::Indexed(1), ..Palette::mono() };
+        assert_eq!("\x1B[39m", marked.escape(marked.age(0)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_color_from_invalid() {
+        let _ = Color::from("#ff87");
+    }
+
+    #[test]
+    fn test_classic() {
+        let classic = Palette::classic();
+        let ages = (0..8).map(|age| classic.age(age).escape()).collect::<Vec<_>>();
+        assert_eq!(vec!["\x1B[38;5;34m", "\x1B[38;5;35m", "\x1B[38;5;36m", "\x1B[38;5;37m", "\x1B[38;5;38m", "\x1B[38;5;39m", "\x1B[38;5;21m", "\x1B[38;5;21m"], ages);
+        assert_eq!(Color::Indexed(226), classic.decay(2, 4));
+        assert_eq!(Color::Indexed(202), classic.decay(3, 4));
+        assert_eq!(Color::Indexed(124), classic.decay(8, 8));
+    }
+
+    #[test]
+    fn test_gradient() {
+        let gradient = Palette { ages: vec![Color::from("#000000"), Color::from("#c8c8c8"), Color::from("#ff0000")], ..Palette::classic() }
+            .with_max_age(100);
+        assert_eq!(Color::Rgb([0, 0, 0]), gradient.age(0));
+        assert_eq!(Color::Rgb([100, 100, 100]), gradient.age(25));
+        assert_eq!(Color::Rgb([200, 200, 200]), gradient.age(50));
+        assert_eq!(Color::Rgb([255, 0, 0]), gradient.age(100));
+        assert_eq!(Color::Rgb([255, 0, 0]), gradient.age(1000));
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let palettes = Palette::parse_config("\
+# Comment
+[sunset]
+ages = #ffcc00 #ff6600 #990066
+max-age = 20
+
+[plain]
+ages = 15
+marked = default
+");
+        let sunset = &palettes["sunset"];
+        assert_eq!(Color::Rgb([255, 204, 0]), sunset.age(0));
+        assert_eq!(Color::Rgb([153, 0, 102]), sunset.age(20));
+        assert_eq!(Palette::classic().decay(2, 3), sunset.decay(2, 3));
+
+        assert_eq!(Color::Indexed(15), palettes["plain"].age(3));
+        assert_eq!(Color::Default, palettes["plain"].marked());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_parse_config_unknown_key() {
+        let _ = Palette::parse_config("[broken]\ncolours = 1 2 3");
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use std::thread;
+
+use crate::bitfield::{count_bits, select};
+use crate::field::Field;
+use crate::rule::AutomataRule;
+
+const CHUNK: i64 = 64;
+
+/// 64x64 cells, one word per row with bit `c` holding column `c`.
+type Chunk = [u64; CHUNK as usize];
+
+/// Unbounded plane made of bit-packed chunks that are only allocated where cells are alive.
+///
+/// Row `r` and column `c` of a `Field` are mapped to `(x, y) = (c, r)`.
+pub struct Plane {
+    chunks: HashMap<(i64, i64), Chunk>,
+    rule: AutomataRule,
+}
+
+impl Plane {
+    pub fn new(rule: AutomataRule) -> Plane {
+        if rule.apply(false, 0) {
+            panic!("Rules with B0 are not supported on an unbounded plane");
+        }
+        if !rule.is_outer_totalistic() {
+            panic!("Only totalistic rules on the Moore neighbourhood are supported by the sparse engine");
+        }
+        if rule.states() > 2 {
+            panic!("Generations rules are not supported by the sparse engine");
+        }
+        Plane { chunks: HashMap::new(), rule }
+    }
+
+    /// Starts with the live cells of `field`, its top left cell at (`left`, `top`).
+    pub fn from_field(field: &Field<bool>, top: i64, left: i64, rule: AutomataRule) -> Plane {
+        let mut plane = Plane::new(rule);
+        for (i, _) in field.cells.iter().enumerate().filter(|(_, &alive)| alive) {
+            plane.set(left + (i % field.columns) as i64, top + (i / field.columns) as i64, true);
+        }
+        plane
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, alive: bool) {
+        let key = (x.div_euclid(CHUNK), y.div_euclid(CHUNK));
+        let bit = 1u64 << x.rem_euclid(CHUNK);
+        let row = y.rem_euclid(CHUNK) as usize;
+
+        if alive {
+            self.chunks.entry(key).or_insert([0; CHUNK as usize])[row] |= bit;
+        } else if let Some(chunk) = self.chunks.get_mut(&key) {
+            chunk[row] &= !bit;
+            if chunk.iter().all(|&w| w == 0) {
+                self.chunks.remove(&key);
+            }
+        }
+    }
+
+    /// Advances one generation. Only chunks that are alive or touch a live chunk are computed.
+    pub fn step(&mut self, threads: usize) {
+        let candidates = self.chunks.keys()
+            .flat_map(|&(cx, cy)| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| (cx + dx, cy + dy))))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let per_thread = candidates.len().div_ceil(threads.max(1)).max(1);
+        let current = &*self;
+
+        let chunks = thread::scope(|s| {
+            let workers = candidates.chunks(per_thread).map(|keys| s.spawn(move || {
+                keys.iter()
+                    .map(|&key| (key, current.step_chunk(key)))
+                    .filter(|(_, chunk)| chunk.iter().any(|&w| w != 0))
+                    .collect::<Vec<_>>()
+            })).collect::<Vec<_>>();
+
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+        });
+
+        self.chunks = chunks;
+    }
+
+    fn step_chunk(&self, (cx, cy): (i64, i64)) -> Chunk {
+        let (birth, survival) = self.rule.tables();
+        let empty = [0; CHUNK as usize];
+        let chunk = |dx, dy| self.chunks.get(&(cx + dx, cy + dy)).unwrap_or(&empty);
+
+        // Columns -1, 0 and 1 of chunks in rows -1, 0 and 1 around this one.
+        let around = [
+            [chunk(-1, -1), chunk(0, -1), chunk(1, -1)],
+            [chunk(-1, 0), chunk(0, 0), chunk(1, 0)],
+            [chunk(-1, 1), chunk(0, 1), chunk(1, 1)],
+        ];
+
+        let mut next = [0; CHUNK as usize];
+        for (r, word) in next.iter_mut().enumerate() {
+            // West, centre and east words of the row `dr` above or below `r`.
+            let row = |dr: i64| {
+                let rr = r as i64 + dr;
+                let band = &around[(1 + rr.div_euclid(CHUNK)) as usize];
+                let rr = rr.rem_euclid(CHUNK) as usize;
+                let (w, c, e) = (band[0][rr], band[1][rr], band[2][rr]);
+                ((c << 1) | (w >> (CHUNK - 1)), c, (c >> 1) | (e << (CHUNK - 1)))
+            };
+
+            let (up_w, up, up_e) = row(-1);
+            let (w, alive, e) = row(0);
+            let (down_w, down, down_e) = row(1);
+
+            let count = count_bits([up_w, up, up_e, w, e, down_w, down, down_e]);
+            *word = (alive & select(&count, survival)) | (!alive & select(&count, birth));
+        }
+
+        next
+    }
+
+    /// Copies the `rows` x `columns` window whose top left cell is (`left`, `top`) into a field.
+    pub fn to_field(&self, top: i64, left: i64, rows: usize, columns: usize) -> Field<bool> {
+        let mut field = Field::with_size(rows, columns);
+        let (bottom, right) = (top + rows as i64, left + columns as i64);
+
+        for (&(cx, cy), chunk) in self.chunks.iter() {
+            let (x0, y0) = (cx * CHUNK, cy * CHUNK);
+            if x0 >= right || y0 >= bottom || x0 + CHUNK <= left || y0 + CHUNK <= top {
+                continue;
+            }
+
+            for y in y0.max(top)..(y0 + CHUNK).min(bottom) {
+                let word = chunk[(y - y0) as usize];
+                for x in x0.max(left)..(x0 + CHUNK).min(right) {
+                    if word >> (x - x0) & 1 == 1 {
+                        field.cells[(y - top) as usize * columns + (x - left) as usize] = true;
+                    }
+                }
+            }
+        }
+
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::Field;
+    use crate::plane::Plane;
+    use crate::rule::AutomataRule;
+
+    #[test]
+    fn test_matches_naive() {
+        let rule = AutomataRule::cgol();
+        let mut soup = Field::with_size(160, 160);
+        soup.insert(Field::from_random(24, 24), 60, 60);
+
+        // Shifted so the soup straddles the chunk boundaries at -64, 0 and 64.
+        let mut plane = Plane::from_field(&soup, -70, -70, rule.clone());
+
+        for threads in [1, 4].iter() {
+            for _ in 0..20 {
+                plane.step(*threads);
+                soup = Field::new(soup.next_generation(&rule, 1), 160, 160);
+                assert!(soup == plane.to_field(-70, -70, 160, 160));
+            }
+        }
+    }
+
+    #[test]
+    fn test_glider_never_returns() {
+        let glider = Field::from_cells("\
+.O.
+..O
+OOO");
+        let mut plane = Plane::from_field(&glider, 0, 0, AutomataRule::cgol());
+        for _ in 0..400 {
+            plane.step(2);
+        }
+
+        let population: u32 = plane.chunks.values().flat_map(|chunk| chunk.iter()).map(|row| row.count_ones()).sum();
+        assert_eq!(5, population);
+        assert!(glider == plane.to_field(100, 100, 3, 3));
+        assert_eq!(0, plane.to_field(0, 0, 100, 100).cells.iter().filter(|&&c| c).count());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unsupported_rule() {
+        Plane::new(AutomataRule::from("/2/3"));
+    }
+}
@@ -1,8 +1,116 @@
 use regex::Regex;
 
+use crate::ltl::LtlRule;
+
+/// Hensel's letters for 1 to 4 neighbours, each with one representative configuration.
+/// Bits 0 to 7 of a configuration are the N, NE, E, SE, S, SW, W and NW neighbours.
+/// The letters for 5 to 7 neighbours name the complements of those for 3 to 1.
+const HENSEL: [(usize, char, u8); 31] = [
+    (1, 'c', 0b0000_0010), (1, 'e', 0b0000_0001),
+    (2, 'c', 0b0000_1010), (2, 'e', 0b0000_0101), (2, 'k', 0b0000_1001),
+    (2, 'a', 0b0000_0011), (2, 'i', 0b0001_0001), (2, 'n', 0b0010_0010),
+    (3, 'c', 0b0010_1010), (3, 'e', 0b0001_0101), (3, 'k', 0b0010_0101), (3, 'a', 0b0000_0111),
+    (3, 'i', 0b1000_0011), (3, 'n', 0b0000_1011), (3, 'y', 0b0010_1001), (3, 'q', 0b0010_0011),
+    (3, 'j', 0b0100_0011), (3, 'r', 0b0001_0011),
+    (4, 'c', 0b1010_1010), (4, 'e', 0b0101_0101), (4, 'k', 0b0010_1101), (4, 'a', 0b0000_1111),
+    (4, 'i', 0b0001_1011), (4, 'n', 0b0010_1110), (4, 'y', 0b0010_1011), (4, 'q', 0b0010_0111),
+    (4, 'j', 0b0011_0101), (4, 'r', 0b0001_0111), (4, 't', 0b0011_1001), (4, 'w', 0b0011_0110),
+    (4, 'z', 0b0011_0011),
+];
+
+/// Offsets `(row, column)` of the cells counted as neighbours.
+pub const MOORE: [(i32, i32); 8] = [
+    (-1, -1), (-1, 0), (-1, 1),
+    (0, -1), /*     */ (0, 1),
+    (1, -1), (1, 0), (1, 1),
+];
+pub const VON_NEUMANN: [(i32, i32); 4] = [
+    /*    */ (-1, 0),
+    (0, -1), /*  */ (0, 1),
+    /*    */ (1, 0),
+];
+/// Golly's hexagonal emulation: the Moore neighbourhood without the NE and SW corners.
+pub const HEXAGONAL: [(i32, i32); 6] = [
+    (-1, -1), (-1, 0),
+    (0, -1), /*     */ (0, 1),
+    /*           */ (1, 0), (1, 1),
+];
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Neighbourhood {
+    Moore,
+    VonNeumann,
+    Hexagonal,
+    /// Arbitrary offsets, e.g. parsed from a mask like `.O./O.O/.O.`.
+    Custom(Vec<(i32, i32)>),
+}
+
+impl Neighbourhood {
+    pub fn offsets(&self) -> &[(i32, i32)] {
+        match self {
+            Neighbourhood::Moore => &MOORE,
+            Neighbourhood::VonNeumann => &VON_NEUMANN,
+            Neighbourhood::Hexagonal => &HEXAGONAL,
+            Neighbourhood::Custom(offsets) => offsets,
+        }
+    }
+
+    /// Maximum number of live neighbours.
+    pub fn size(&self) -> usize {
+        self.offsets().len()
+    }
+
+    /// Largest distance of a neighbour along either axis.
+    pub fn radius(&self) -> usize {
+        self.offsets().iter().map(|&(dr, dc)| dr.unsigned_abs().max(dc.unsigned_abs()) as usize).max().unwrap_or(0)
+    }
+}
+
+impl From<&str> for Neighbourhood {
+    /// Either a name or a mask of `O` (counted) and `.` cells with rows separated by `/`,
+    /// centred on the cell itself.
+    fn from(txt: &str) -> Self {
+        match txt {
+            "moore" => return Neighbourhood::Moore,
+            "von-neumann" => return Neighbourhood::VonNeumann,
+            "hexagonal" => return Neighbourhood::Hexagonal,
+            _ => {}
+        }
+
+        let rows = txt.split('/').collect::<Vec<_>>();
+        let (height, width) = (rows.len(), rows[0].len());
+        if height % 2 == 0 || width % 2 == 0 || rows.iter().any(|row| row.len() != width) {
+            panic!("Neighbourhood mask \"{}\" must be a rectangle with an odd number of rows and columns", txt);
+        }
+
+        let mut offsets = Vec::new();
+        for (r, row) in rows.iter().enumerate() {
+            for (c, cell) in row.chars().enumerate() {
+                match cell {
+                    'O' => offsets.push((r as i32 - height as i32 / 2, c as i32 - width as i32 / 2)),
+                    '.' => {}
+                    _ => panic!("Unknown neighbourhood \"{}\"", txt),
+                }
+            }
+        }
+        // Rules are written with a single digit per neighbour count.
+        if offsets.len() > 8 {
+            panic!("Neighbourhood mask \"{}\" has {} cells, rules can only count up to 8", txt, offsets.len());
+        }
+        Neighbourhood::Custom(offsets)
+    }
+}
+
+#[derive(Clone)]
 pub struct AutomataRule {
-    b: [bool; 9],
-    s: [bool; 9],
+    /// Birth and survival per number of live neighbours, from 0 to the neighbourhood's size.
+    b: Vec<bool>,
+    s: Vec<bool>,
+    /// Birth and survival per neighbourhood configuration, only set for non-totalistic rules.
+    configurations: Option<Box<([bool; 256], [bool; 256])>>,
+    /// Number of cell states; more than two makes this a Generations rule with dying states.
+    states: u8,
+    neighbourhood: Neighbourhood,
 }
 
 impl AutomataRule {
@@ -13,37 +121,241 @@ impl AutomataRule {
     pub fn apply(&self, alive: bool, neighbours: usize) -> bool {
         (alive & self.s[neighbours]) | (!alive & self.b[neighbours])
     }
+
+    /// Applies the rule to a neighbourhood configuration as laid out in `HENSEL`.
+    pub fn apply_configuration(&self, alive: bool, configuration: u8) -> bool {
+        match self.configurations.as_deref() {
+            Some((b, s)) => (alive & s[configuration as usize]) | (!alive & b[configuration as usize]),
+            None => self.apply(alive, configuration.count_ones() as usize),
+        }
+    }
+
+    /// Next Generations state of a cell in `state`, where `alive` is the result of `apply`.
+    /// State 0 is dead, 1 is alive and every higher state is dying; dying cells ignore `alive`.
+    pub fn next_state(&self, state: u8, alive: bool) -> u8 {
+        generations_state(state, alive, self.states)
+    }
+
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// Whether the rule only depends on the number of live neighbours.
+    pub fn is_totalistic(&self) -> bool {
+        self.configurations.is_none()
+    }
+
+    pub fn tables(&self) -> (&[bool], &[bool]) {
+        (&self.b, &self.s)
+    }
+
+    pub fn neighbourhood(&self) -> &Neighbourhood {
+        &self.neighbourhood
+    }
+
+    /// Whether the rule counts Moore neighbours only, as the bit-sliced engines require.
+    pub fn is_outer_totalistic(&self) -> bool {
+        self.is_totalistic() && self.neighbourhood == Neighbourhood::Moore
+    }
+
+    /// Counts `neighbourhood` instead, keeping the enabled neighbour counts.
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> Self {
+        let size = neighbourhood.size();
+        if !self.is_totalistic() && neighbourhood != Neighbourhood::Moore {
+            panic!("Non-totalistic rules need the Moore neighbourhood");
+        }
+        let beyond = |table: &[bool]| table.iter().skip(size + 1).any(|&on| on);
+        if beyond(&self.b) || beyond(&self.s) {
+            panic!("Neighbour counts above {} are impossible in the {:?} neighbourhood", size, neighbourhood);
+        }
+
+        self.b.resize(size + 1, false);
+        self.s.resize(size + 1, false);
+        self.neighbourhood = neighbourhood;
+        self
+    }
 }
 
 impl From<&str> for AutomataRule {
     fn from(txt: &str) -> Self {
-        let re = Regex::new(r"B([0-8]+)?/S([0-8]+)?").unwrap();
-        if !re.is_match(txt) {
+        let conditions = r"((?:[0-8]-?[cekainyqjrtwz]*)*)";
+        let life = Regex::new(&format!("^B{}/S{}([HV]?)$", conditions, conditions)).unwrap();
+        let generations = Regex::new(&format!("^B{}/S{}/C([0-9]+)([HV]?)$", conditions, conditions)).unwrap();
+        let golly = Regex::new(&format!("^{}/{}/([0-9]+)([HV]?)$", conditions, conditions)).unwrap();
+
+        // Golly's Generations notation lists survival first, e.g. 345/2/4 for Star Wars.
+        // A trailing H or V selects the hexagonal or von Neumann neighbourhood.
+        let (birth, survival, states, suffix) = if let Some(c) = life.captures(txt) {
+            (c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str(), "2", c.get(3).unwrap().as_str())
+        } else if let Some(c) = generations.captures(txt) {
+            (c.get(1).unwrap().as_str(), c.get(2).unwrap().as_str(), c.get(3).unwrap().as_str(), c.get(4).unwrap().as_str())
+        } else if let Some(c) = golly.captures(txt) {
+            (c.get(2).unwrap().as_str(), c.get(1).unwrap().as_str(), c.get(3).unwrap().as_str(), c.get(4).unwrap().as_str())
+        } else {
             panic!("Unknown rule format \"{}\"", txt);
-        }
+        };
 
-        let vec: Vec<&str> = txt.split('/').collect();
+        let states = match states.parse::<u8>() {
+            Ok(states) if states >= 2 => states,
+            _ => panic!("Unsupported number of states in \"{}\"", txt),
+        };
 
         let mut b = [false; 9];
         let mut s = [false; 9];
+        let mut b_configurations = [false; 256];
+        let mut s_configurations = [false; 256];
+
+        let b_totalistic = parse_conditions(birth, &mut b, &mut b_configurations)
+            .unwrap_or_else(|| panic!("Unknown rule format \"{}\"", txt));
+        let s_totalistic = parse_conditions(survival, &mut s, &mut s_configurations)
+            .unwrap_or_else(|| panic!("Unknown rule format \"{}\"", txt));
+
+        let configurations = match b_totalistic && s_totalistic {
+            true => None,
+            false => Some(Box::new((b_configurations, s_configurations))),
+        };
+
+        let neighbourhood = match suffix {
+            "H" => Neighbourhood::Hexagonal,
+            "V" => Neighbourhood::VonNeumann,
+            _ => Neighbourhood::Moore,
+        };
+
+        AutomataRule { b: b.to_vec(), s: s.to_vec(), configurations, states, neighbourhood: Neighbourhood::Moore }
+            .with_neighbourhood(neighbourhood)
+    }
+}
+
+/// Any rule the game runs: life-like, isotropic and Generations rules on a neighbourhood, or Larger
+/// than Life rules counting a whole range. Both kinds have dying states when they have more than two.
+#[derive(Clone)]
+pub enum Rule {
+    Automata(AutomataRule),
+    Ltl(LtlRule),
+}
+
+impl Rule {
+    pub fn states(&self) -> u8 {
+        match self {
+            Rule::Automata(rule) => rule.states(),
+            Rule::Ltl(rule) => rule.states(),
+        }
+    }
+
+    /// How far a cell's state reaches in one generation.
+    pub fn reach(&self) -> usize {
+        match self {
+            Rule::Automata(rule) => rule.neighbourhood().radius(),
+            Rule::Ltl(rule) => rule.range(),
+        }
+    }
+
+    pub fn next_state(&self, state: u8, alive: bool) -> u8 {
+        match self {
+            Rule::Automata(rule) => rule.next_state(state, alive),
+            Rule::Ltl(rule) => rule.next_state(state, alive),
+        }
+    }
+
+    /// Counts `neighbourhood` instead; Larger than Life rules have their own and are rejected.
+    pub fn with_neighbourhood(self, neighbourhood: Neighbourhood) -> Self {
+        match self {
+            Rule::Automata(rule) => Rule::Automata(rule.with_neighbourhood(neighbourhood)),
+            Rule::Ltl(_) => panic!("Larger than Life rules set their neighbourhood with N, not with a mask"),
+        }
+    }
+}
+
+impl From<AutomataRule> for Rule {
+    fn from(rule: AutomataRule) -> Self {
+        Rule::Automata(rule)
+    }
+}
+
+/// Larger than Life rules start with their range, e.g. `R5,C0,M1,S34..58,B34..45,NM`.
+impl From<&str> for Rule {
+    fn from(txt: &str) -> Self {
+        match txt.starts_with('R') {
+            true => Rule::Ltl(LtlRule::from(txt)),
+            false => Rule::Automata(AutomataRule::from(txt)),
+        }
+    }
+}
+
+/// Next state of a cell in `state` out of `states`, see `AutomataRule::next_state`.
+pub fn generations_state(state: u8, alive: bool, states: u8) -> u8 {
+    match state {
+        0 => alive as u8,
+        1 if alive => 1,
+        _ if state + 1 < states => state + 1,
+        _ => 0,
+    }
+}
+
+/// Parses conditions like `2-a3ij4` into `counts` and `configurations`.
+/// Returns whether only whole neighbour counts were given, or `None` for an unknown letter.
+fn parse_conditions(txt: &str, counts: &mut [bool; 9], configurations: &mut [bool; 256]) -> Option<bool> {
+    let mut totalistic = true;
+    let mut chars = txt.chars().peekable();
+
+    while let Some(digit) = chars.next() {
+        let n = digit.to_digit(10)? as usize;
+        let negated = chars.next_if_eq(&'-').is_some();
+        let mut letters = Vec::new();
+        while let Some(letter) = chars.next_if(|c| c.is_ascii_lowercase()) {
+            letters.push(letter);
+        }
+
+        let mut selected = [letters.is_empty() || negated; 256];
+        for letter in letters {
+            totalistic = false;
+            for configuration in hensel_class(n, letter)? {
+                selected[configuration as usize] = !negated;
+            }
+        }
+
+        counts[n] = selected.iter().enumerate().all(|(c, &on)| on || (c as u8).count_ones() as usize != n);
+        for (c, configuration) in configurations.iter_mut().enumerate() {
+            if (c as u8).count_ones() as usize == n && selected[c] {
+                *configuration = true;
+            }
+        }
+    }
 
-        vec[0].chars().skip(1).map(|c| c.to_digit(10).unwrap() as usize).for_each(|i| b[i] = true);
-        vec[1].chars().skip(1).map(|c| c.to_digit(10).unwrap() as usize).for_each(|i| s[i] = true);
+    Some(totalistic)
+}
+
+/// All rotations and reflections of the configuration named by `n` and `letter`.
+fn hensel_class(n: usize, letter: char) -> Option<Vec<u8>> {
+    let lookup = if n > 4 { 8 - n } else { n };
+    let &(_, _, representative) = HENSEL.iter().find(|&&(count, l, _)| count == lookup && l == letter)?;
+    let representative = if n > 4 { !representative } else { representative };
+
+    let rotate = |c: u8| c.rotate_left(2);
+    let reflect = |c: u8| (0..8).filter(|i| c >> i & 1 == 1).fold(0u8, |acc, i| acc | 1 << ((8 - i) % 8));
 
-        AutomataRule { b, s }
+    let mut class = Vec::new();
+    let mut c = representative;
+    for _ in 0..4 {
+        class.push(c);
+        class.push(reflect(c));
+        c = rotate(c);
     }
+    class.sort_unstable();
+    class.dedup();
+    Some(class)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::field::Field;
-    use crate::rule::AutomataRule;
+    use crate::rule::{AutomataRule, hensel_class, HENSEL, Neighbourhood, Rule, VON_NEUMANN};
 
     #[test]
     fn test_from() {
         let r = AutomataRule::from("B3/S23");
-        assert_eq!([false, false, false, true, false, false, false, false, false], r.b);
-        assert_eq!([false, false, true, true, false, false, false, false, false], r.s);
+        assert_eq!([false, false, false, true, false, false, false, false, false], r.b[..]);
+        assert_eq!([false, false, true, true, false, false, false, false, false], r.s[..]);
     }
 
     #[test]
@@ -51,10 +363,155 @@ mod tests {
         let r = AutomataRule::from("B3/S23");
         let glider = Field::from_rle("x=5,y=5,rule=B3/S23\nbob$2bo$3o!");
         let projection = glider.proj2d();
-        let neighbours = glider.calculate_neighbours(&projection);
+        let neighbours = glider.calculate_neighbours(&projection, r.neighbourhood());
         let result = Field::new(glider.cells.iter().zip(neighbours).map(|(&alive, n)| r.apply(alive, n)).collect(), 5, 5);
         let expected = Field::from_rle("x=5,y=5,rule=B3/S23\n$obo$b2o$bo2$!");
 
         assert!(expected == result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hensel_classes_partition_neighbourhoods() {
+        let mut seen = [0; 256];
+        seen[0] += 1;
+        seen[255] += 1;
+        for n in 1..8 {
+            for &(_, letter, _) in HENSEL.iter().filter(|&&(count, _, _)| count == n.min(8 - n)) {
+                for c in hensel_class(n, letter).unwrap() {
+                    assert_eq!(n as u32, c.count_ones(), "{}{}", n, letter);
+                    seen[c as usize] += 1;
+                }
+            }
+        }
+        assert!(seen.iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn test_from_hensel() {
+        let r = AutomataRule::from("B2-a/S12");
+        assert!(!r.is_totalistic());
+        assert!(r.apply_configuration(false, 0b0001_0001));
+        assert!(!r.apply_configuration(false, 0b0000_0011));
+        assert!(!r.apply_configuration(false, 0b1100_0000));
+        assert!(r.apply_configuration(true, 0b1000_0000));
+        assert!(!r.apply_configuration(true, 0b1000_0011));
+
+        let r = AutomataRule::from("B3/S23-a4i");
+        assert!(r.apply_configuration(true, 0b0010_0010));
+        assert!(!r.apply_configuration(true, 0b0000_0111));
+        assert!(r.apply_configuration(true, 0b0001_1011));
+        assert!(!r.apply_configuration(true, 0b0000_1111));
+        assert_eq!([false, false, true, false, false, false, false, false, false], r.s[..]);
+    }
+
+    #[test]
+    fn test_hensel_without_letters_is_totalistic() {
+        let r = AutomataRule::from("B36/S23");
+        let h = AutomataRule::from("B3-c3c6/S2-k2k3");
+        assert!(r.is_totalistic());
+        assert!(!h.is_totalistic());
+        for c in 0..=255u8 {
+            for &alive in [false, true].iter() {
+                assert_eq!(r.apply_configuration(alive, c), h.apply_configuration(alive, c));
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_generations() {
+        let brain = AutomataRule::from("/2/3");
+        assert_eq!([false, false, true, false, false, false, false, false, false], brain.b[..]);
+        assert_eq!([false; 9], brain.s[..]);
+        assert_eq!(3, brain.states());
+
+        let star_wars = AutomataRule::from("345/2/4");
+        let star_wars_bsc = AutomataRule::from("B2/S345/C4");
+        for r in [star_wars, star_wars_bsc].iter() {
+            assert_eq!([false, false, true, false, false, false, false, false, false], r.b[..]);
+            assert_eq!([false, false, false, true, true, true, false, false, false], r.s[..]);
+            assert_eq!(4, r.states());
+        }
+    }
+
+    #[test]
+    fn test_next_state() {
+        let r = AutomataRule::from("B2/S345/C4");
+        assert_eq!(1, r.next_state(0, true));
+        assert_eq!(0, r.next_state(0, false));
+        assert_eq!(1, r.next_state(1, true));
+        assert_eq!(2, r.next_state(1, false));
+        assert_eq!(3, r.next_state(2, true));
+        assert_eq!(0, r.next_state(3, true));
+
+        let life = AutomataRule::cgol();
+        assert_eq!(0, life.next_state(1, false));
+        assert_eq!(1, life.next_state(0, true));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_unknown_letter() {
+        let _ = AutomataRule::from("B1a/S23");
+    }
+
+    #[test]
+    fn test_from_neighbourhood_suffix() {
+        let hex = AutomataRule::from("B2/S34H");
+        assert_eq!(Neighbourhood::Hexagonal, *hex.neighbourhood());
+        assert_eq!([false, false, true, false, false, false, false], hex.b[..]);
+        assert_eq!([false, false, false, true, true, false, false], hex.s[..]);
+
+        let von_neumann = AutomataRule::from("B1/S1V");
+        assert_eq!(Neighbourhood::VonNeumann, *von_neumann.neighbourhood());
+        assert_eq!([false, true, false, false, false], von_neumann.b[..]);
+        assert!(!von_neumann.is_outer_totalistic());
+
+        let brain = AutomataRule::from("/2/3H");
+        assert_eq!(3, brain.states());
+        assert_eq!(Neighbourhood::Hexagonal, *brain.neighbourhood());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_count_beyond_neighbourhood() {
+        let _ = AutomataRule::from("B5/S1V");
+    }
+
+    #[test]
+    fn test_neighbourhood_mask() {
+        assert_eq!(Neighbourhood::Custom(VON_NEUMANN.to_vec()), Neighbourhood::from(".O./O.O/.O."));
+
+        let knight = Neighbourhood::from(".O.O./O...O/...../O...O/.O.O.");
+        assert_eq!(8, knight.size());
+        assert_eq!(2, knight.radius());
+        assert!(knight.offsets().contains(&(-2, -1)));
+
+        let r = AutomataRule::from("B3/S23").with_neighbourhood(knight);
+        assert_eq!(9, r.tables().0.len());
+        let r = r.with_neighbourhood(Neighbourhood::from("OOOOO"));
+        assert_eq!([false, false, false, true, false, false], r.b[..]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_neighbourhood_mask_too_large() {
+        let _ = Neighbourhood::from("OOOOO/O...O/OOOOO");
+    }
+
+    #[test]
+    fn test_rule() {
+        let bosco = Rule::from("R5,C0,M1,S34..58,B34..45,NM");
+        assert!(matches!(bosco, Rule::Ltl(_)));
+        assert_eq!((2, 5), (bosco.states(), bosco.reach()));
+
+        let brain = Rule::from("/2/3").with_neighbourhood(Neighbourhood::VonNeumann);
+        assert!(matches!(brain, Rule::Automata(_)));
+        assert_eq!((3, 1, 2), (brain.states(), brain.reach(), brain.next_state(1, false)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rule_ltl_neighbourhood() {
+        let _ = Rule::from("R5,C0,M1,S34..58,B34..45,NM").with_neighbourhood(Neighbourhood::VonNeumann);
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+use crate::term::{gfx_hline, gfx_pos1};
+
+/// One screenful of text output: the field between two lines and a status line below.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Frame {
+    pub rows: usize,
+    pub columns: usize,
+    /// Character and colour escape sequence of every position, row by row.
+    pub cells: Vec<(char, String)>,
+    /// Colour escape sequence of the lines and the status.
+    pub line: String,
+    pub status: String,
+}
+
+impl Frame {
+    /// Escape sequences turning `previous` into this frame by moving the cursor to changed characters only.
+    /// Returns `None` if the size differs or more than half of the characters changed.
+    pub fn diff(&self, previous: &Frame) -> Option<String> {
+        if (self.rows, self.columns) != (previous.rows, previous.columns) {
+            return None;
+        }
+        let changed = self.cells.iter().zip(&previous.cells)
+            .enumerate()
+            .filter(|(_, ((gfx, color), (previous_gfx, previous_color)))| gfx != previous_gfx || (*gfx != ' ' && color != previous_color))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if changed.len() * 2 > self.cells.len() {
+            return None;
+        }
+
+        let mut output = String::new();
+        let mut current_color = "";
+        let mut cursor = None;
+        for i in changed {
+            let (gfx, color) = &self.cells[i];
+            if cursor != Some(i) {
+                output += &format!("\x1B[{};{}H", i / self.columns + 2, i % self.columns + 1);
+            }
+            if *gfx != ' ' && color != current_color {
+                output += color;
+                current_color = color;
+            }
+            output.push(*gfx);
+            cursor = Some(i + 1).filter(|&next| next % self.columns != 0);
+        }
+
+        if self.status != previous.status {
+            output += &format!("\x1B[{};1H{}{}\x1B[K", self.rows + 3, self.line, self.status);
+        }
+        Some(output)
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut output = String::new();
+        let hline = self.line.clone() + &gfx_hline(self.columns);
+        output += gfx_pos1();
+        output += hline.as_str();
+        output += "\n";
+
+        let mut current_color = "";
+        for row in self.cells.chunks(self.columns.max(1)) {
+            for (gfx, color) in row {
+                if *gfx != ' ' && color != current_color {
+                    output += color;
+                    current_color = color;
+                }
+                output.push(*gfx);
+            }
+            output += "\n";
+        }
+        output += hline.as_str();
+        output += "\n";
+        output += self.status.as_str();
+        f.write_str(output.as_str())
+    }
+}
+
+/// What the terminal shows, to draw the next frame as the difference to the last one.
+#[derive(Default)]
+pub struct Screen {
+    previous: Option<Frame>,
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen { previous: None }
+    }
+
+    /// Forgets the last frame, e.g. after the screen was cleared, so the next one is drawn in full.
+    pub fn invalidate(&mut self) {
+        self.previous = None;
+    }
+
+    /// Draws `frame` as changes to the last one, or in full if there is none or most of it changed.
+    pub fn draw(&mut self, frame: Frame) -> String {
+        let output = self.previous.as_ref()
+            .and_then(|previous| frame.diff(previous))
+            .unwrap_or_else(|| frame.to_string());
+        self.previous = Some(frame);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::screen::{Frame, Screen};
+
+    fn frame(text: &str, status: &str) -> Frame {
+        let lines = text.lines().collect::<Vec<_>>();
+        let cells = lines.iter()
+            .flat_map(|line| line.chars().map(|c| (c, format!("\x1B[38;5;{}m", if c == 'x' { 1 } else { 34 }))))
+            .collect();
+        Frame { rows: lines.len(), columns: lines[0].len(), cells, line: "\x1B[38;5;15m".to_string(), status: status.to_string() }
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = frame("O...\n....\n.OO.", "1");
+        assert_eq!(Some(String::new()), previous.diff(&previous));
+
+        let next = frame("O...\n...O\n.Ox.", "1");
+        assert_eq!(Some("\x1B[3;4H\x1B[38;5;34mO\x1B[4;3H\x1B[38;5;1mx".to_string()), next.diff(&previous));
+
+        let next = frame("O.OO\nO...\n.OO.", "10");
+        assert_eq!(Some("\x1B[2;3H\x1B[38;5;34mOO\x1B[3;1HO\x1B[6;1H\x1B[38;5;15m10\x1B[K".to_string()), next.diff(&previous));
+
+        assert_eq!(None, frame("OOOO\nOOOO\n....", "1").diff(&previous));
+        assert_eq!(None, frame("O...\n....", "1").diff(&previous));
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut screen = Screen::new();
+        let first = frame("O.\n.O", "1");
+        assert_eq!(first.to_string(), screen.draw(first.clone()));
+        assert_eq!("\x1B[5;1H\x1B[38;5;15m2\x1B[K", screen.draw(frame("O.\n.O", "2")));
+
+        screen.invalidate();
+        assert!(screen.draw(frame("O.\n.O", "2")).starts_with("\x1B[1;1H"));
+    }
+}
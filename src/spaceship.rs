@@ -0,0 +1,117 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::field::Field;
+use crate::object::objects;
+
+/// An object that reappeared `dx` columns to the right and `dy` rows down after `period` generations.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Spaceship {
+    pub dx: i64,
+    pub dy: i64,
+    pub period: usize,
+}
+
+/// The speed, e.g. `(1,1)c/4` for a glider moving down and to the right.
+impl fmt::Display for Spaceship {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({},{})c/{}", self.dx, self.dy, self.period)
+    }
+}
+
+/// Where an object was in a generation, with the move it made since it was last seen, if any.
+struct Sighting {
+    row: usize,
+    column: usize,
+    moved: Option<Spaceship>,
+}
+
+/// Objects by shape in the most recent generations, at most `window` of them.
+pub struct ShipDetector {
+    generations: VecDeque<(usize, HashMap<u64, Vec<Sighting>>)>,
+    window: usize,
+}
+
+impl ShipDetector {
+    pub fn new(window: usize) -> ShipDetector {
+        ShipDetector { generations: VecDeque::new(), window }
+    }
+
+    /// Records the objects of the generation at `iterations` and returns the spaceships among them.
+    /// An object moved if the closest one of the same shape in the latest generation that had one is
+    /// elsewhere, but no further than light travels with `reach`. It only counts as a spaceship once
+    /// it made the same move twice in a row, so that pieces of larger objects recurring by chance don't.
+    pub fn push(&mut self, states: &Field<u8>, reach: usize, iterations: usize) -> Vec<Spaceship> {
+        let topology = states.topology;
+        let mut current = HashMap::<u64, Vec<Sighting>>::new();
+        let mut ships = Vec::new();
+
+        for object in objects(states, reach) {
+            let hash = object.shape_hash();
+            let mut moved = None;
+            for (seen, past) in self.generations.iter().rev() {
+                let period = iterations - seen;
+                let limit = (period * reach.max(1)) as u64;
+                let closest = past.get(&hash).into_iter().flatten()
+                    .map(|sighting| {
+                        let dy = shift(object.row, sighting.row, states.rows, topology.wraps_rows());
+                        let dx = shift(object.column, sighting.column, states.columns, topology.wraps_columns());
+                        (dy, dx, sighting.moved)
+                    })
+                    .filter(|&(dy, dx, _)| dy.unsigned_abs() <= limit && dx.unsigned_abs() <= limit)
+                    .min_by_key(|&(dy, dx, _)| dy.abs() + dx.abs());
+
+                if let Some((dy, dx, before)) = closest {
+                    moved = Some(Spaceship { dx, dy, period }).filter(|_| (dy, dx) != (0, 0));
+                    if moved.is_some() && moved == before {
+                        ships.extend(moved);
+                    }
+                    break;
+                }
+            }
+            current.entry(hash).or_default().push(Sighting { row: object.row, column: object.column, moved });
+        }
+        ships.sort_unstable();
+        ships.dedup();
+
+        if self.generations.len() == self.window {
+            self.generations.pop_front();
+        }
+        self.generations.push_back((iterations, current));
+        ships
+    }
+
+    /// Forgets all generations, e.g. after the field was edited.
+    pub fn clear(&mut self) {
+        self.generations.clear();
+    }
+}
+
+/// Signed distance from `from` to `to` along an axis of `length` cells, the shorter way round if it wraps.
+fn shift(to: usize, from: usize, length: usize, wraps: bool) -> i64 {
+    let d = to as i64 - from as i64;
+    match wraps {
+        true => {
+            let d = d.rem_euclid(length as i64);
+            if d > length as i64 / 2 { d - length as i64 } else { d }
+        }
+        false => d,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spaceship::{shift, Spaceship};
+
+    #[test]
+    fn test_shift() {
+        assert_eq!(-1, shift(9, 0, 10, true));
+        assert_eq!(9, shift(9, 0, 10, false));
+        assert_eq!(2, shift(1, 9, 10, true));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!("(0,-1)c/10", Spaceship { dx: 0, dy: -1, period: 10 }.to_string());
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::game::Game;
+use crate::spaceship::Spaceship;
+use crate::topology::Topology;
+
+/// How far back `Meter` looks when measuring the speed.
+const WINDOW: Duration = Duration::from_secs(2);
+
+/// Measures generations per second over the last couple of seconds.
+#[derive(Default)]
+pub struct Meter {
+    ticks: VecDeque<(Instant, usize)>,
+}
+
+impl Meter {
+    pub fn new() -> Meter {
+        Meter { ticks: VecDeque::new() }
+    }
+
+    /// Records that the game reached `iterations` just now.
+    pub fn tick(&mut self, iterations: usize) {
+        self.tick_at(Instant::now(), iterations);
+    }
+
+    /// Records that the game reached `iterations` at `now`, starting over if it went back in time.
+    pub fn tick_at(&mut self, now: Instant, iterations: usize) {
+        if self.ticks.back().is_some_and(|&(_, last)| last > iterations) {
+            self.ticks.clear();
+        }
+        self.ticks.push_back((now, iterations));
+        while self.ticks.front().is_some_and(|&(time, _)| now.duration_since(time) > WINDOW) {
+            self.ticks.pop_front();
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        match (self.ticks.front(), self.ticks.back()) {
+            (Some(&(start, first)), Some(&(end, last))) if end > start => (last - first) as f64 / (end - start).as_secs_f64(),
+            _ => 0.0,
+        }
+    }
+}
+
+/// The line below the field, e.g. `Gen 42 | Pop 312 +12 -9 | B3/S23 | 29.8/33.3 gen/s | 80x21 torus | 3 marked | period 2`.
+pub struct Status<'a> {
+    generation: usize,
+    population: usize,
+    births: usize,
+    deaths: usize,
+    rule: &'a str,
+    rate: f64,
+    /// Generations per second the interval aims for, `None` without an interval.
+    target: Option<f64>,
+    paused: bool,
+    rows: usize,
+    columns: usize,
+    topology: Topology,
+    matches: Option<usize>,
+    period: Option<usize>,
+    ships: Vec<Spaceship>,
+    /// E.g. why the field couldn't be saved.
+    message: Option<&'a str>,
+}
+
+impl<'a> Status<'a> {
+    /// Statistics of `game` running `rule` at `rate` generations per second every `interval` milliseconds.
+    pub fn of(game: &Game, rule: &'a str, rate: f64, interval: u64, paused: bool) -> Status<'a> {
+        let (births, deaths) = game.births_deaths();
+        let field = game.field();
+        Status {
+            generation: game.iterations(),
+            population: game.population(),
+            births,
+            deaths,
+            rule,
+            rate,
+            target: Some(interval).filter(|&i| i > 0).map(|i| game.generations() as f64 * 1000.0 / i as f64),
+            paused,
+            rows: field.rows,
+            columns: field.columns,
+            topology: field.topology,
+            matches: game.matches(),
+            period: game.period(),
+            ships: game.ships().to_vec(),
+            message: None,
+        }
+    }
+
+    /// Ends the line with `message`, if any.
+    pub fn with_message(mut self, message: Option<&'a str>) -> Self {
+        self.message = message;
+        self
+    }
+}
+
+impl fmt::Display for Status<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Gen {} | Pop {} +{} -{} | {} | ", self.generation, self.population, self.births, self.deaths, self.rule)?;
+        match (self.paused, self.target) {
+            (true, _) => write!(f, "paused")?,
+            (false, Some(target)) => write!(f, "{:.1}/{:.1} gen/s", self.rate, target)?,
+            (false, None) => write!(f, "{:.1} gen/s", self.rate)?,
+        }
+        write!(f, " | {}x{} {}", self.columns, self.rows, self.topology.name())?;
+        if let Some(matches) = self.matches {
+            write!(f, " | {} marked", matches)?;
+        }
+        match self.period {
+            Some(1) => write!(f, " | still life")?,
+            Some(period) => write!(f, " | period {}", period)?,
+            None => {}
+        }
+        if !self.ships.is_empty() {
+            write!(f, " | ship {}", self.ships.iter().map(Spaceship::to_string).collect::<Vec<_>>().join(", "))?;
+        }
+        if let Some(message) = self.message {
+            write!(f, " | {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use futures::executor::block_on;
+
+    use crate::field::Field;
+    use crate::game::Game;
+    use crate::rule::AutomataRule;
+    use crate::status::{Meter, Status};
+
+    #[test]
+    fn test_meter() {
+        let mut meter = Meter::new();
+        let start = Instant::now();
+        assert_eq!(0.0, meter.rate());
+
+        for i in 0..=10 {
+            meter.tick_at(start + Duration::from_millis(100 * i), i as usize * 3);
+        }
+        assert!((meter.rate() - 30.0).abs() < 1e-9);
+
+        meter.tick_at(start + Duration::from_millis(5000), 60);
+        assert_eq!(0.0, meter.rate());
+        meter.tick_at(start + Duration::from_millis(5500), 10);
+        assert_eq!(0.0, meter.rate());
+    }
+
+    #[test]
+    fn test_status() {
+        let mut game = Game::new(Field::from_cells(".....\n..O..\n..O..\n..O..\n....."), AutomataRule::cgol());
+        block_on(game.next_iteration());
+
+        let status = Status::of(&game, "B3/S23", 29.84, 30, false).to_string();
+        assert_eq!("Gen 1 | Pop 3 +2 -2 | B3/S23 | 29.8/33.3 gen/s | 5x5 torus", status);
+
+        game.mark_pattern(&Field::pattern_from_cells("OOO"));
+        let status = Status::of(&game, "B3/S23", 0.0, 0, true).to_string();
+        assert_eq!("Gen 1 | Pop 3 +2 -2 | B3/S23 | paused | 5x5 torus | 1 marked", status);
+        let status = Status::of(&game, "B3/S23", 0.0, 0, true).with_message(Some("Couldn't save")).to_string();
+        assert!(status.ends_with("| 1 marked | Couldn't save"));
+
+        block_on(game.next_iteration());
+        let status = Status::of(&game, "B3/S23", 0.0, 0, false).to_string();
+        assert_eq!("Gen 2 | Pop 3 +2 -2 | B3/S23 | 0.0 gen/s | 5x5 torus | 1 marked | period 2", status);
+
+        let mut glider = Game::new(Field::from_cells(".O...\n..O..\nOOO..\n.....\n.....\n....."), AutomataRule::cgol()).with_ship_detection(true);
+        for _ in 0..8 {
+            block_on(glider.next_iteration());
+        }
+        let status = Status::of(&glider, "B3/S23", 0.0, 0, false).to_string();
+        assert_eq!("Gen 8 | Pop 5 +2 -2 | B3/S23 | 0.0 gen/s | 5x6 torus | ship (1,1)c/4", status);
+    }
+}
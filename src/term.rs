@@ -1,22 +1,21 @@
-use std::process::Command;
-
-pub fn colormap_gb(n: u32) -> String {
-    match n {
-        0 => String::from("\x1B[38;5;34m"),
-        1 => String::from("\x1B[38;5;35m"),
-        2 => String::from("\x1B[38;5;36m"),
-        3 => String::from("\x1B[38;5;37m"),
-        4 => String::from("\x1B[38;5;38m"),
-        5 => String::from("\x1B[38;5;39m"),
-        _ => String::from("\x1B[38;5;21m")
-    }
-}
+use std::io::{stdout, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-#[allow(unused)]
-pub fn colormap_rgb(n: u32) -> String {
-    match n < 256 {
-        true => format!("\x1b[38;2;128;{};128m", 255 - n),
-        false => String::from("\x1b[38;2;128;0;128m"),
+/// Red, green and blue of an xterm 256 colour index.
+pub fn xterm_rgb(index: u8) -> [u8; 3] {
+    const BASIC: [[u8; 3]; 16] = [
+        [0, 0, 0], [205, 0, 0], [0, 205, 0], [205, 205, 0], [0, 0, 238], [205, 0, 205], [0, 205, 205], [229, 229, 229],
+        [127, 127, 127], [255, 0, 0], [0, 255, 0], [255, 255, 0], [92, 92, 255], [255, 0, 255], [0, 255, 255], [255, 255, 255],
+    ];
+    let level = |v: u8| if v == 0 { 0 } else { 55 + 40 * v };
+    match index {
+        0..=15 => BASIC[index as usize],
+        16..=231 => {
+            let i = index - 16;
+            [level(i / 36), level(i / 6 % 6), level(i % 6)]
+        }
+        _ => [8 + 10 * (index - 232); 3],
     }
 }
 
@@ -28,6 +27,28 @@ pub const fn gfx_pos1() -> &'static str {
     "\x1B[1;1H"
 }
 
+pub const fn gfx_hide_cursor() -> &'static str {
+    "\x1B[?25l"
+}
+
+/// Moves the cursor to the 1-based `row` and `column` and shows it.
+pub fn gfx_cursor_at(row: usize, column: usize) -> String {
+    format!("\x1B[{};{}H\x1B[?25h", row, column)
+}
+
+/// Turns xterm SGR reporting of mouse presses and drags on or off.
+pub const fn gfx_mouse(enable: bool) -> &'static str {
+    match enable {
+        true => "\x1B[?1002h\x1B[?1006h",
+        false => "\x1B[?1002l\x1B[?1006l",
+    }
+}
+
+/// Resets colours, turns off mouse reporting, shows the cursor and starts a new line.
+pub const fn gfx_reset() -> &'static str {
+    "\x1B[0m\x1B[?1002l\x1B[?1006l\x1B[?25h\n"
+}
+
 pub fn gfx_cell(alive: bool) -> &'static str {
     match alive {
         true => "\u{2588}",
@@ -56,16 +77,90 @@ pub fn gfx_cell_highres(ul: bool, ur: bool, bl: bool, br: bool) -> &'static str
     }
 }
 
-pub fn gfx_hline(columns: usize) -> String {
-    "\x1B[38;5;15m".to_string() + "\u{25AC}".repeat(columns).as_str()
+/// Braille pattern for a block of 4 rows by 2 columns of cells, a space if all of them are dead.
+pub fn gfx_cell_braille(cells: [[bool; 2]; 4]) -> char {
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    let bits = (0..4).flat_map(|r| (0..2).map(move |c| (r, c)))
+        .filter(|&(r, c)| cells[r][c])
+        .fold(0, |bits, (r, c)| bits | DOTS[r][c]);
+    match bits {
+        0 => ' ',
+        _ => char::from_u32(0x2800 + bits).unwrap(),
+    }
+}
+
+/// Shade for `alive` out of `cells` cells, any live cell showing at least the lightest one.
+pub fn gfx_density(alive: usize, cells: usize) -> &'static str {
+    const SHADES: [&str; 5] = [" ", "\u{2591}", "\u{2592}", "\u{2593}", "\u{2588}"];
+    SHADES[(alive * 4).div_ceil(cells.max(1)).min(4)]
 }
 
-pub fn gfx_hline_highres(columns: usize) -> String {
-    "\x1B[38;5;15m".to_string() + "\u{25AC}".repeat(columns / 2).as_str()
+pub fn gfx_hline(columns: usize) -> String {
+    "\u{25AC}".repeat(columns)
 }
 
 pub(crate) fn call(cmd: &str, arg: &str) -> Option<String> {
     let output = Command::new(cmd).arg(arg).output().ok()?;
     let string = String::from_utf8_lossy(&output.stdout).trim_end().to_string();
     Some(string)
-}
\ No newline at end of file
+}
+
+/// Unbuffered terminal input without echo for as long as it lives, restoring the previous
+/// settings when dropped. Ctrl+C arrives as a byte instead of a signal.
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    /// Returns `None` if stdin is not a terminal.
+    pub fn enter() -> Option<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        Some(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[&self.saved]);
+    }
+}
+
+/// Writes `gfx_reset` when dropped, so that colours, mouse reporting and the cursor are restored
+/// however the program ends, with or without a terminal on stdin.
+pub struct ResetGuard;
+
+impl Drop for ResetGuard {
+    fn drop(&mut self) {
+        let mut stdout = stdout();
+        let _ = stdout.write_all(gfx_reset().as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+/// Runs `stty` on the terminal attached to stdin.
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output().ok()?;
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).trim_end().to_string()),
+        false => None,
+    }
+}
+
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, Ordering::Relaxed);
+}
+
+/// Notes SIGWINCH from now on, see `take_resized`.
+pub fn watch_resize() {
+    unsafe {
+        libc::signal(libc::SIGWINCH, on_resize as *const () as libc::sighandler_t);
+    }
+}
+
+/// Whether the terminal was resized since the last call.
+pub fn take_resized() -> bool {
+    RESIZED.swap(false, Ordering::Relaxed)
+}
@@ -0,0 +1,118 @@
+/// How the edges of a bounded field are glued together.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Topology {
+    /// Left/right and top/bottom edges are joined.
+    Torus,
+    /// Everything beyond the edges is dead.
+    Plane,
+    /// Left and right edges are joined, top and bottom are dead.
+    CylinderH,
+    /// Top and bottom edges are joined, left and right are dead.
+    CylinderV,
+    /// Left and right edges are joined, top and bottom are joined with the columns mirrored.
+    Klein,
+    /// Both pairs of edges are joined mirrored (real projective plane).
+    CrossSurface,
+}
+
+impl Topology {
+    /// Maps a possibly out of bounds position to the cell it refers to, or `None` if it is outside.
+    pub fn resolve(&self, row: i64, column: i64, rows: usize, columns: usize) -> Option<(usize, usize)> {
+        let (r, c) = (rows as i64, columns as i64);
+        let inside = |p: i64, lim: i64| p >= 0 && p < lim;
+        let flip = |p: i64, lim: i64, crossings: i64| if crossings % 2 != 0 { lim - 1 - p } else { p };
+
+        match self {
+            Topology::Torus => Some((wrap_i64(row, r), wrap_i64(column, c))),
+            Topology::Plane if inside(row, r) && inside(column, c) => Some((row as usize, column as usize)),
+            Topology::CylinderH if inside(row, r) => Some((row as usize, wrap_i64(column, c))),
+            Topology::CylinderV if inside(column, c) => Some((wrap_i64(row, r), column as usize)),
+            Topology::Klein => {
+                let column = flip(column, c, row.div_euclid(r));
+                Some((wrap_i64(row, r), wrap_i64(column, c)))
+            }
+            Topology::CrossSurface => {
+                let row = flip(row, r, column.div_euclid(c));
+                let column = flip(column.rem_euclid(c), c, row.div_euclid(r));
+                Some((wrap_i64(row, r), column as usize))
+            }
+            _ => None,
+        }
+    }
+
+    /// Offsets `(row, column)` by `(dr, dc)` and resolves the result.
+    pub fn offset(&self, (row, column): (usize, usize), (dr, dc): (i32, i32), rows: usize, columns: usize) -> Option<(usize, usize)> {
+        self.resolve(row as i64 + dr as i64, column as i64 + dc as i64, rows, columns)
+    }
+
+    /// The name `From<&str>` accepts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topology::Torus => "torus",
+            Topology::Plane => "plane",
+            Topology::CylinderH => "cylinder-h",
+            Topology::CylinderV => "cylinder-v",
+            Topology::Klein => "klein",
+            Topology::CrossSurface => "cross-surface",
+        }
+    }
+
+    pub fn wraps_columns(&self) -> bool {
+        matches!(self, Topology::Torus | Topology::CylinderH)
+    }
+
+    pub fn wraps_rows(&self) -> bool {
+        matches!(self, Topology::Torus | Topology::CylinderV)
+    }
+
+    /// Whether crossing an edge mirrors the other axis.
+    pub fn is_twisted(&self) -> bool {
+        matches!(self, Topology::Klein | Topology::CrossSurface)
+    }
+}
+
+impl From<&str> for Topology {
+    fn from(txt: &str) -> Self {
+        match txt {
+            "torus" => Topology::Torus,
+            "plane" => Topology::Plane,
+            "cylinder-h" => Topology::CylinderH,
+            "cylinder-v" => Topology::CylinderV,
+            "klein" => Topology::Klein,
+            "cross-surface" => Topology::CrossSurface,
+            unknown => panic!("Unknown topology \"{}\"", unknown)
+        }
+    }
+}
+
+fn wrap_i64(pos: i64, lim: i64) -> usize {
+    pos.rem_euclid(lim) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::topology::Topology;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Some((4, 0)), Topology::Torus.resolve(-1, 5, 5, 5));
+        assert_eq!(None, Topology::Plane.resolve(-1, 2, 5, 5));
+        assert_eq!(Some((0, 4)), Topology::Plane.resolve(0, 4, 5, 5));
+        assert_eq!(Some((2, 4)), Topology::CylinderH.resolve(2, -1, 5, 5));
+        assert_eq!(None, Topology::CylinderH.resolve(5, 1, 5, 5));
+        assert_eq!(Some((0, 1)), Topology::CylinderV.resolve(5, 1, 5, 5));
+        assert_eq!(None, Topology::CylinderV.resolve(1, -1, 5, 5));
+    }
+
+    #[test]
+    fn test_resolve_twisted() {
+        // Leaving through the bottom edge re-enters at the top with the column mirrored.
+        assert_eq!(Some((0, 3)), Topology::Klein.resolve(6, 1, 6, 5));
+        assert_eq!(Some((5, 4)), Topology::Klein.resolve(-1, 0, 6, 5));
+        assert_eq!(Some((2, 0)), Topology::Klein.resolve(2, 5, 6, 5));
+
+        assert_eq!(Some((0, 3)), Topology::CrossSurface.resolve(6, 1, 6, 5));
+        assert_eq!(Some((3, 0)), Topology::CrossSurface.resolve(2, 5, 6, 5));
+        assert_eq!(Some((5, 4)), Topology::CrossSurface.resolve(0, -1, 6, 5));
+    }
+}
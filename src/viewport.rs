@@ -0,0 +1,196 @@
+/// Cells per terminal character along each axis, from 1:1 over highres 2:1 to density views.
+const ZOOMS: [usize; 4] = [1, 2, 4, 8];
+
+/// The part of the field shown on the terminal, `rows` x `columns` characters of `zoom` x `zoom`
+/// cells each whose top left cell is (`top`, `left`). With `braille` the 2:1 level packs 2x4 cells
+/// into Braille patterns instead of 2x2 into quadrants. Over an unbounded plane the field is a
+/// window onto it whose top left cell lies at the plane coordinates `origin`, which panning moves.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+    pub top: usize,
+    pub left: usize,
+    pub rows: usize,
+    pub columns: usize,
+    pub zoom: usize,
+    pub braille: bool,
+    pub origin: Option<(i64, i64)>,
+}
+
+impl Viewport {
+    pub fn new(rows: usize, columns: usize, zoom: usize) -> Viewport {
+        Viewport { top: 0, left: 0, rows, columns, zoom, braille: false, origin: None }
+    }
+
+    pub fn with_braille(mut self) -> Viewport {
+        self.braille = true;
+        self
+    }
+
+    /// Pans over an unbounded plane, starting with the field's top left cell at its origin.
+    pub fn with_plane(mut self) -> Viewport {
+        self.origin = Some((0, 0));
+        self
+    }
+
+    /// Cells per character as `(rows, columns)`.
+    pub fn scale(&self) -> (usize, usize) {
+        match self.braille && self.zoom == 2 {
+            true => (4, 2),
+            false => (self.zoom, self.zoom),
+        }
+    }
+
+    /// Changes the size of the view to `rows` x `columns` characters over a field of `field_rows` x `field_columns`.
+    pub fn resize(&mut self, rows: usize, columns: usize, field_rows: usize, field_columns: usize) {
+        self.rows = rows;
+        self.columns = columns;
+        self.clamp(field_rows, field_columns);
+    }
+
+    /// Characters actually covered by a field of `rows` x `columns` cells.
+    pub fn visible(&self, rows: usize, columns: usize) -> (usize, usize) {
+        let (sr, sc) = self.scale();
+        let cover = |cells: usize, start: usize, scale: usize, limit: usize| cells.saturating_sub(start).div_ceil(scale).min(limit);
+        (cover(rows, self.top, sr, self.rows), cover(columns, self.left, sc, self.columns))
+    }
+
+    /// Moves by a quarter of the view in the direction of `(dr, dc)`, staying inside the field
+    /// unless it is a window onto a plane.
+    pub fn pan(&mut self, (dr, dc): (i32, i32), rows: usize, columns: usize) {
+        let (sr, sc) = self.scale();
+        let step = |chars: usize, scale: usize| (chars * scale / 4).max(scale) as i64;
+        let (down, right) = (dr as i64 * step(self.rows, sr), dc as i64 * step(self.columns, sc));
+        if let Some((top, left)) = self.origin {
+            self.origin = Some((top + down, left + right));
+            return;
+        }
+        self.top = (self.top as i64 + down).max(0) as usize;
+        self.left = (self.left as i64 + right).max(0) as usize;
+        self.clamp(rows, columns);
+    }
+
+    /// Switches to the next zoom level, wrapping from the densest back to 1:1.
+    pub fn cycle_zoom(&mut self, rows: usize, columns: usize) {
+        let level = ZOOMS.iter().position(|&z| z == self.zoom).map_or(0, |i| (i + 1) % ZOOMS.len());
+        self.set_zoom(ZOOMS[level], rows, columns);
+    }
+
+    pub fn set_zoom(&mut self, zoom: usize, rows: usize, columns: usize) {
+        self.zoom = zoom;
+        self.clamp(rows, columns);
+    }
+
+    /// Scrolls just far enough to show the cell at `row` and `column`.
+    pub fn follow(&mut self, row: usize, column: usize, rows: usize, columns: usize) {
+        let (sr, sc) = self.scale();
+        let (height, width) = (self.rows * sr, self.columns * sc);
+        self.top = self.top.min(row).max((row + 1).saturating_sub(height));
+        self.left = self.left.min(column).max((column + 1).saturating_sub(width));
+        self.clamp(rows, columns);
+    }
+
+    /// Cell at the top left of the character at the 1-based terminal position (`x`, `y`),
+    /// below the line at the top.
+    pub fn cell_at(&self, x: usize, y: usize, rows: usize, columns: usize) -> Option<(usize, usize)> {
+        let (r, c) = (y.checked_sub(2)?, x.checked_sub(1)?);
+        if r >= self.rows || c >= self.columns {
+            return None;
+        }
+        let (sr, sc) = self.scale();
+        let (row, column) = (self.top + r * sr, self.left + c * sc);
+        match row < rows && column < columns {
+            true => Some((row, column)),
+            false => None,
+        }
+    }
+
+    /// 1-based terminal position of the character showing the cell at `row` and `column`.
+    pub fn position_of(&self, row: usize, column: usize) -> (usize, usize) {
+        let (sr, sc) = self.scale();
+        ((row - self.top) / sr + 2, (column - self.left) / sc + 1)
+    }
+
+    fn clamp(&mut self, rows: usize, columns: usize) {
+        let (sr, sc) = self.scale();
+        self.top = self.top.min(rows.saturating_sub(self.rows * sr));
+        self.left = self.left.min(columns.saturating_sub(self.columns * sc));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::viewport::Viewport;
+
+    #[test]
+    fn test_pan_and_zoom() {
+        let mut viewport = Viewport::new(10, 20, 1);
+        viewport.pan((1, 1), 100, 100);
+        assert_eq!((2, 5), (viewport.top, viewport.left));
+        viewport.pan((-1, 0), 100, 100);
+        viewport.pan((-1, 0), 100, 100);
+        assert_eq!(0, viewport.top);
+
+        viewport.cycle_zoom(100, 100);
+        viewport.pan((0, 100), 100, 100);
+        assert_eq!((2, 60), (viewport.zoom, viewport.left));
+
+        viewport.cycle_zoom(100, 100);
+        viewport.cycle_zoom(100, 100);
+        assert_eq!((8, 0), (viewport.zoom, viewport.left));
+        assert_eq!((10, 13), viewport.visible(100, 100));
+        viewport.cycle_zoom(100, 100);
+        assert_eq!(1, viewport.zoom);
+    }
+
+    #[test]
+    fn test_cell_at() {
+        let mut viewport = Viewport::new(10, 20, 4);
+        viewport.pan((1, 1), 200, 200);
+        assert_eq!((10, 20), (viewport.top, viewport.left));
+        assert_eq!(Some((10, 20)), viewport.cell_at(1, 2, 200, 200));
+        assert_eq!(Some((14, 28)), viewport.cell_at(3, 3, 200, 200));
+        assert_eq!(None, viewport.cell_at(21, 2, 200, 200));
+        assert_eq!(None, viewport.cell_at(1, 2, 5, 5));
+        assert_eq!((3, 3), viewport.position_of(15, 31));
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut viewport = Viewport::new(10, 20, 1);
+        viewport.pan((0, 1), 100, 100);
+        viewport.resize(10, 98, 100, 100);
+        assert_eq!((0, 2), (viewport.top, viewport.left));
+        assert_eq!((10, 98), viewport.visible(100, 100));
+    }
+
+    #[test]
+    fn test_braille() {
+        let mut viewport = Viewport::new(10, 20, 2).with_braille();
+        assert_eq!((4, 2), viewport.scale());
+        assert_eq!((10, 20), viewport.visible(100, 100));
+        assert_eq!((5, 20), viewport.visible(20, 100));
+        viewport.pan((1, 1), 100, 100);
+        assert_eq!((10, 10), (viewport.top, viewport.left));
+        assert_eq!(Some((14, 12)), viewport.cell_at(2, 3, 100, 100));
+        assert_eq!((3, 2), viewport.position_of(17, 13));
+        viewport.cycle_zoom(100, 100);
+        assert_eq!((4, 4), viewport.scale());
+    }
+
+    #[test]
+    fn test_pan_plane() {
+        let mut viewport = Viewport::new(10, 20, 1).with_plane();
+        viewport.pan((-1, 1), 10, 20);
+        viewport.pan((-1, 0), 10, 20);
+        assert_eq!((Some((-4, 5)), 0, 0), (viewport.origin, viewport.top, viewport.left));
+    }
+
+    #[test]
+    fn test_follow() {
+        let mut viewport = Viewport::new(10, 20, 2);
+        viewport.follow(25, 3, 100, 100);
+        assert_eq!((6, 0), (viewport.top, viewport.left));
+        viewport.follow(2, 99, 100, 100);
+        assert_eq!((2, 60), (viewport.top, viewport.left));
+    }
+}